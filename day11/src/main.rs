@@ -1,99 +1,320 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io;
+use std::io::Write;
 
+// Pass --animate to play the octopuses up to the step where they all flash
+// together in the terminal, or --gif <file> to save the same as a GIF.
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
     println!("part1 is {}", part1(&lines));
-    println!("part2 is {}", part2(&lines));
+    let sync = part2(&lines);
+    println!("part2 is {}", sync);
+
+    let args = env::args().collect::<Vec<_>>();
+    // play a whole cycle if they never synchronise
+    let steps = match sync {
+        Sync::At(steps) => steps,
+        Sync::Never { start, length } => start + length,
+    };
+    if args.iter().any(|a| a == "--animate") {
+        animate(&mut parse(&lines), steps, &mut io::stdout()).unwrap();
+    }
+    if let Some(i) = args.iter().position(|a| a == "--gif") {
+        let filename = args.get(i + 1).expect("--gif needs a file name");
+        let mut out = io::BufWriter::new(fs::File::create(filename).unwrap());
+        write_gif(&mut parse(&lines), steps, &mut out).unwrap();
+    }
 }
 
-type Grid = [[i32; 10]; 10];
+struct Grid {
+    vals: Vec<Vec<i32>>,
+    height: usize,
+    width: usize,
+    // whether the edges wrap around, so every octopus has 8 neighbours
+    wrap: bool,
+    // octopuses with more energy than this flash
+    threshold: i32,
+}
 
-fn neighbors(i: usize, j: usize) -> Vec<(usize, usize)> {
-    (-1..=1)
+fn neighbors(g: &Grid, i: usize, j: usize) -> Vec<(usize, usize)> {
+    let mut ret = (-1..=1)
         .flat_map(|e| std::iter::repeat(e).zip(-1..=1))
         .filter(|d| d.0 != 0 || d.1 != 0)
         .map(|d| (i as i32 + d.0, j as i32 + d.1))
-        .filter(|p| (0..10).contains(&p.0))
-        .filter(|p| (0..10).contains(&p.1))
+        .filter_map(|p| match g.wrap {
+            true => Some((
+                p.0.rem_euclid(g.height as i32),
+                p.1.rem_euclid(g.width as i32),
+            )),
+            false if (0..g.height as i32).contains(&p.0) && (0..g.width as i32).contains(&p.1) => {
+                Some(p)
+            }
+            false => None,
+        })
         .map(|p| (p.0 as usize, p.1 as usize))
-        .collect()
+        // on small wrapping grids the same cell can be reached twice, or be
+        // the octopus itself
+        .filter(|p| *p != (i, j))
+        .collect::<Vec<_>>();
+    ret.sort_unstable();
+    ret.dedup();
+    ret
 }
 
+// The octopuses that flashed during one step, in waves: the first wave was
+// pushed over the threshold by the step itself, and each later wave by the
+// flashes of the wave before.
+type Cascade = Vec<Vec<(usize, usize)>>;
+
 fn step(g: &mut Grid) -> i32 {
-    let mut flashed = [[false; 10]; 10];
-    let mut to_inc = (0..10)
-        .flat_map(|e| std::iter::repeat(e).zip(0..10))
+    step_traced(g).iter().map(|w| w.len() as i32).sum()
+}
+
+fn step_traced(g: &mut Grid) -> Cascade {
+    let mut flashed = vec![vec![false; g.width]; g.height];
+    let mut to_inc = (0..g.height)
+        .flat_map(|e| std::iter::repeat(e).zip(0..g.width))
         .collect::<Vec<_>>();
+    let mut cascade = vec![];
 
-    while to_inc.len() > 0 {
-        let (i, j) = to_inc.pop().unwrap();
-        g[i][j] += 1;
-        if !flashed[i][j] && g[i][j] > 9 {
-            flashed[i][j] = true;
-            to_inc.extend(neighbors(i, j));
+    while !to_inc.is_empty() {
+        let mut wave = vec![];
+        for (i, j) in to_inc.drain(..) {
+            g.vals[i][j] += 1;
+            if !flashed[i][j] && g.vals[i][j] > g.threshold {
+                flashed[i][j] = true;
+                wave.push((i, j));
+            }
+        }
+        for (i, j) in &wave {
+            to_inc.extend(neighbors(g, *i, *j));
+        }
+        if !wave.is_empty() {
+            wave.sort_unstable();
+            cascade.push(wave);
+        }
+    }
+
+    for (row, flashed_row) in g.vals.iter_mut().zip(&flashed) {
+        for (val, flashed) in row.iter_mut().zip(flashed_row) {
+            if *flashed {
+                *val = 0;
+            }
         }
     }
 
-    let mut flashes = 0;
+    cascade
+}
 
-    for i in 0..10 {
-        for j in 0..10 {
-            if flashed[i][j] {
-                flashes += 1;
-                g[i][j] = 0;
+// How a cell is shown in one frame of an animation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Shade {
+    Energy(i32),
+    // flashed in an earlier wave of this step
+    Flashed,
+    // flashing in the wave being shown
+    Flashing,
+}
+
+// Steps g steps times, calling frame with the cells for every wave of every
+// step and for the grid after each step, along with a caption.
+fn frames<F>(g: &mut Grid, steps: usize, mut frame: F) -> io::Result<()>
+where
+    F: FnMut(&str, &[Vec<Shade>]) -> io::Result<()>,
+{
+    let shades = |g: &Grid| {
+        g.vals
+            .iter()
+            .map(|row| row.iter().map(|v| Shade::Energy(*v)).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    frame("start", &shades(g))?;
+    for s in 1..=steps {
+        let mut cells = shades(g);
+        let cascade = step_traced(g);
+        for (w, wave) in cascade.iter().enumerate() {
+            if w > 0 {
+                for (i, j) in &cascade[w - 1] {
+                    cells[*i][*j] = Shade::Flashed;
+                }
             }
+            for (i, j) in wave {
+                cells[*i][*j] = Shade::Flashing;
+            }
+            frame(&format!("step {} wave {}", s, w + 1), &cells)?;
         }
+        frame(&format!("after step {}", s), &shades(g))?;
     }
+    Ok(())
+}
 
-    return flashes;
+// Plays steps steps as ANSI coloured text, redrawing the screen each frame.
+fn animate<W: Write>(g: &mut Grid, steps: usize, out: &mut W) -> io::Result<()> {
+    let threshold = g.threshold;
+    frames(g, steps, |caption, cells| {
+        writeln!(out, "\x1b[H\x1b[2J{}", caption)?;
+        for row in cells {
+            for cell in row {
+                match cell {
+                    Shade::Energy(v) if *v > threshold => write!(out, "\x1b[1;33m*")?,
+                    Shade::Energy(0) => write!(out, "\x1b[1;97m0")?,
+                    Shade::Energy(v) => write!(out, "\x1b[2;37m{}", v)?,
+                    Shade::Flashed => write!(out, "\x1b[33m*")?,
+                    Shade::Flashing => write!(out, "\x1b[1;97;43m*")?,
+                }
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+        out.flush()?;
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        Ok(())
+    })
 }
 
-fn parse(lines: &[&str]) -> [[i32; 10]; 10] {
-    let mut g = [[0; 10]; 10];
+// Saves steps steps as an animated GIF, with each octopus drawn as a square
+// of SCALE pixels.
+fn write_gif<W: Write>(g: &mut Grid, steps: usize, out: &mut W) -> io::Result<()> {
+    const SCALE: usize = 8;
+    // 0..=9 are energy from dark blue to pale blue, then flashed and flashing
+    let mut palette = (0..10)
+        .map(|v| [(v * 16) as u8, (v * 20) as u8, (100 + v * 15) as u8])
+        .collect::<Vec<_>>();
+    palette.push([255, 160, 0]);
+    palette.push([255, 255, 255]);
 
-    for i in 0..10 {
-        let mut j = 0;
-        lines[i].chars().for_each(|c| {
-            g[i][j] = c as i32 - '0' as i32;
-            j += 1
-        });
+    let (height, width) = (g.height * SCALE, g.width * SCALE);
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let size =
+        |n: usize| u16::try_from(n).map_err(|_| invalid(format!("{} is too big for a GIF", n)));
+    let (gif_height, gif_width) = (size(height)?, size(width)?);
+    let threshold = g.threshold;
+    if threshold <= 0 {
+        return Err(invalid(format!(
+            "can't shade energy with threshold {}",
+            threshold
+        )));
     }
+    let mut images = vec![];
+    frames(g, steps, |_, cells| {
+        let mut image = vec![0; height * width];
+        for (r, pixels) in image.chunks_mut(width).enumerate() {
+            for (c, p) in pixels.iter_mut().enumerate() {
+                *p = match cells[r / SCALE][c / SCALE] {
+                    Shade::Energy(v) => (v * 9 / threshold).clamp(0, 9) as u8,
+                    Shade::Flashed => 10,
+                    Shade::Flashing => 11,
+                };
+            }
+        }
+        images.push(image);
+        Ok(())
+    })?;
+    gif::write(out, gif_width, gif_height, &palette, &images, 10)
+}
+
+fn parse(lines: &[&str]) -> Grid {
+    let vals = lines
+        .iter()
+        .map(|l| l.chars().map(|c| c as i32 - '0' as i32).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let height = vals.len();
+    let width = vals[0].len();
+    assert!(
+        vals.iter().all(|row| row.len() == width),
+        "grid must be rectangular"
+    );
 
-    g
+    Grid {
+        vals,
+        height,
+        width,
+        wrap: false,
+        threshold: 9,
+    }
 }
 
 fn part1(lines: &[&str]) -> i32 {
     let mut g = parse(lines);
 
-    return (0..100).fold(0, |a, _| {
-        return a + step(&mut g);
-    });
+    flashes_after(&mut g, 100) as i32
 }
 
-fn done(g: &[[i32; 10]; 10]) -> bool {
-    let val = g[0][0];
-    for i in 0..10 {
-        for j in 0..10 {
-            if g[i][j] != val {
-                return false;
-            }
+fn done(g: &Grid) -> bool {
+    let val = g.vals[0][0];
+    g.vals.iter().all(|row| row.iter().all(|v| *v == val))
+}
+
+#[derive(Debug, PartialEq)]
+enum Sync {
+    // every octopus flashes together for the first time on this step
+    At(usize),
+    // the state after step start repeats every length steps without ever
+    // synchronising
+    Never { start: usize, length: usize },
+}
+
+impl fmt::Display for Sync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sync::At(steps) => write!(f, "{}", steps),
+            Sync::Never { start, length } => write!(
+                f,
+                "never synchronises (cycle of length {} starting at step {})",
+                length, start
+            ),
         }
     }
-    return true;
 }
 
-fn part2(lines: &[&str]) -> i32 {
-    let mut g = parse(lines);
-    let mut steps = 0;
-    loop {
-        steps += 1;
-        step(&mut g);
-        if done(&g) {
-            return steps;
+// Steps g until every octopus has the same energy, or until it gets back to
+// a state it has been in before.
+fn synchronise(g: &mut Grid) -> Sync {
+    let mut seen = HashMap::new();
+    seen.insert(g.vals.clone(), 0);
+    for steps in 1.. {
+        step(g);
+        if done(g) {
+            return Sync::At(steps);
+        }
+        if let Some(start) = seen.insert(g.vals.clone(), steps) {
+            return Sync::Never {
+                start,
+                length: steps - start,
+            };
+        }
+    }
+    panic!("unreachable");
+}
+
+// Total flashes over steps steps, skipping ahead once the states repeat.
+fn flashes_after(g: &mut Grid, steps: u64) -> u64 {
+    let mut seen = HashMap::new();
+    seen.insert(g.vals.clone(), 0);
+    // totals[s] is the number of flashes in the first s steps
+    let mut totals = vec![0];
+    for s in 1..=steps as usize {
+        totals.push(totals[s - 1] + step(g) as u64);
+        if let Some(start) = seen.insert(g.vals.clone(), s) {
+            let length = (s - start) as u64;
+            let per_cycle = totals[s] - totals[start];
+            let remaining = steps - start as u64;
+            let partial = totals[start + (remaining % length) as usize] - totals[start];
+            return totals[start] + remaining / length * per_cycle + partial;
         }
     }
+    totals[steps as usize]
+}
+
+fn part2(lines: &[&str]) -> Sync {
+    let mut g = parse(lines);
+    synchronise(&mut g)
 }
 
 #[cfg(test)]
@@ -103,7 +324,7 @@ mod tests {
     struct TestCase<'a> {
         lines: Vec<&'a str>,
         part1: i32,
-        part2: i32,
+        part2: Sync,
     }
 
     #[test]
@@ -122,11 +343,140 @@ mod tests {
                 "5283751526",
             ],
             part1: 1656,
-            part2: 195,
+            part2: Sync::At(195),
         }];
         for t in cases {
             assert_eq!(part1(&t.lines), t.part1, "part 1 test case {:?}", t.lines);
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn cascades() {
+        let mut g = parse(&["11111", "19991", "19191", "19991", "11111"]);
+        let ring = vec![
+            (1, 1),
+            (1, 2),
+            (1, 3),
+            (2, 1),
+            (2, 3),
+            (3, 1),
+            (3, 2),
+            (3, 3),
+        ];
+        assert_eq!(step_traced(&mut g), vec![ring, vec![(2, 2)]]);
+        assert_eq!(step_traced(&mut g), Cascade::new());
+
+        let mut g = parse(&["11111", "19991", "19191", "19991", "11111"]);
+        let mut captions = vec![];
+        frames(&mut g, 2, |caption, cells| {
+            if caption == "step 1 wave 2" {
+                assert_eq!(cells[1][1], Shade::Flashed);
+                assert_eq!(cells[2][2], Shade::Flashing);
+                assert_eq!(cells[0][0], Shade::Energy(1));
+            }
+            captions.push(caption.to_string());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            captions,
+            vec![
+                "start",
+                "step 1 wave 1",
+                "step 1 wave 2",
+                "after step 1",
+                "after step 2"
+            ]
+        );
+
+        let mut g = parse(&["11111", "19991", "19191", "19991", "11111"]);
+        let mut out = vec![];
+        write_gif(&mut g, 2, &mut out).unwrap();
+        assert!(out.starts_with(b"GIF89a\x28\x00\x28\x00"));
+        assert_eq!(out.last(), Some(&0x3b));
+
+        g.threshold = 0;
+        assert!(write_gif(&mut g, 2, &mut vec![]).is_err());
+        let wide = "1".repeat(9000);
+        assert!(write_gif(&mut parse(&[&wide]), 1, &mut vec![]).is_err());
+
+        let mut g = parse(&["11111", "19991", "19191", "19991", "11111"]);
+        let mut out = vec![];
+        animate(&mut g, 1, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.matches("\x1b[H\x1b[2J").count(), 4);
+    }
+
+    #[test]
+    fn cycles() {
+        let lines = [
+            "5483143223",
+            "2745854711",
+            "5264556173",
+            "6141336146",
+            "6357385478",
+            "4167524645",
+            "2176841721",
+            "6882881134",
+            "4846848554",
+            "5283751526",
+        ];
+        assert_eq!(flashes_after(&mut parse(&lines), 100), 1656);
+        let mut g = parse(&lines);
+        let slow = (0..1000).map(|_| step(&mut g) as u64).sum::<u64>();
+        assert_eq!(flashes_after(&mut parse(&lines), 1000), slow);
+        // once synchronised all 100 flash every 10 steps
+        assert_eq!(
+            flashes_after(&mut parse(&lines), 1_000_000_000),
+            slow + (1_000_000_000 - 1000) / 10 * 100
+        );
+
+        let never = part2(&["123"]);
+        assert_eq!(
+            never,
+            Sync::Never {
+                start: 7,
+                length: 9
+            }
+        );
+        assert_eq!(
+            never.to_string(),
+            "never synchronises (cycle of length 9 starting at step 7)"
+        );
+        let mut g = parse(&["123"]);
+        let slow = (0..100).map(|_| step(&mut g) as u64).sum::<u64>();
+        assert_eq!(flashes_after(&mut parse(&["123"]), 100), slow);
+    }
+
+    #[test]
+    fn sizes() {
+        let mut g = parse(&["11111", "19991", "19191", "19991", "11111"]);
+        assert_eq!(step(&mut g), 9);
+        assert_eq!(
+            g.vals,
+            parse(&["34543", "40004", "50005", "40004", "34543"]).vals
+        );
+        assert_eq!(step(&mut g), 0);
+        assert_eq!(
+            g.vals,
+            parse(&["45654", "51115", "61116", "51115", "45654"]).vals
+        );
+
+        let mut g = parse(&["9000"]);
+        step(&mut g);
+        assert_eq!(g.vals, vec![vec![0, 2, 1, 1]]);
+        let mut g = Grid {
+            wrap: true,
+            ..parse(&["9000"])
+        };
+        step(&mut g);
+        assert_eq!(g.vals, vec![vec![0, 2, 1, 2]]);
+
+        let mut g = Grid {
+            threshold: 5,
+            ..parse(&["5", "4"])
+        };
+        assert_eq!(step(&mut g), 2);
+    }
 }
@@ -1,125 +1,491 @@
 use std::collections::HashMap;
-use std::collections::HashSet;
+use std::env;
 use std::fs;
 
+// Pass --paths to print every path instead, along with --twice to allow
+// one small cave to be visited twice as in part 2, --through <cave> to only
+// print paths through that cave and --limit <n> to print at most n paths.
+// Pass --dot <file> to write the cave graph for graphviz, along with --usage
+// to label each passage with how many paths use it (and --twice as above).
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
+
+    let args = env::args().collect::<Vec<_>>();
+    let value = |flag: &str| {
+        args.iter().position(|a| a == flag).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("{} needs a value", flag))
+        })
+    };
+    let policy = match args.iter().any(|a| a == "--twice") {
+        true => Policy::part2(),
+        false => Policy::part1(),
+    };
+    if let Some(out) = value("--dot") {
+        let map = parse(&lines);
+        let usage = match args.iter().any(|a| a == "--usage") {
+            true => Some(edge_usage(&map, &policy)),
+            false => None,
+        };
+        fs::write(out, to_dot(&map, &policy, usage.as_ref()))
+            .unwrap_or_else(|_| panic!("could not write {}", out));
+        return;
+    }
+    if args.iter().any(|a| a == "--paths") {
+        let through = value("--through").map(|s| s.as_str());
+        let limit = value("--limit").map(|n| n.parse().expect("--limit needs a number"));
+        for p in list_paths(&lines, &policy, through, limit) {
+            println!("{}", p);
+        }
+        return;
+    }
+
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
 }
 
 struct Cave {
-    adjacent: Vec<String>,
+    name: String,
+    small: bool,
+    // ids of the caves connected to this one
+    adjacent: Vec<usize>,
 }
 
 impl Cave {
-    fn new() -> Cave {
-        Cave { adjacent: vec![] }
+    fn new(name: &str) -> Cave {
+        Cave {
+            name: name.to_string(),
+            small: name.chars().next().unwrap().is_lowercase(),
+            adjacent: vec![],
+        }
     }
 }
 
-struct Path {
-    cur: String,
-    visited: HashSet<String>,
-    visited_small_twice: bool,
+// Caves are referred to by their index in caves.
+struct Map {
+    caves: Vec<Cave>,
+    ids: HashMap<String, usize>,
 }
 
-type Map = HashMap<String, Cave>;
+impl Map {
+    fn id(&mut self, name: &str) -> usize {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        self.caves.push(Cave::new(name));
+        self.ids.insert(name.to_string(), self.caves.len() - 1);
+        self.caves.len() - 1
+    }
 
-impl Path {
-    fn new(cur: String, visited: HashSet<String>, visited_small_twice: bool) -> Path {
-        Path {
-            cur,
-            visited,
-            visited_small_twice,
+    fn find(&self, name: &str) -> usize {
+        *self
+            .ids
+            .get(name)
+            .unwrap_or_else(|| panic!("no cave {}", name))
+    }
+}
+
+// Which caves a path may go into, and how often.
+struct Policy {
+    start: String,
+    end: String,
+    // most times any small cave may be visited
+    small_limit: u32,
+    // most times any big cave may be visited, if limited
+    big_limit: Option<u32>,
+    // most times particular caves may be visited, overriding the above
+    limits: HashMap<String, u32>,
+    // how many small caves, other than start and end, may be visited once
+    // more than their limit
+    extra_visits: u32,
+}
+
+impl Policy {
+    // small caves at most once
+    fn part1() -> Policy {
+        Policy {
+            start: "start".to_string(),
+            end: "end".to_string(),
+            small_limit: 1,
+            big_limit: None,
+            limits: HashMap::new(),
+            extra_visits: 0,
         }
     }
 
-    // returns # of full paths, new paths to try
-    fn visit(&self, map: &Map, can_visit_twice: bool) -> (i32, Vec<Path>) {
-        let mut paths = vec![];
-        let c = map.get(&self.cur).unwrap();
-        let mut found = 0;
-        for a in &c.adjacent {
-            // are we at the end?
-            if a == "end" {
-                found += 1;
-                continue;
+    // small caves at most once, except for one that can be visited twice
+    fn part2() -> Policy {
+        Policy {
+            extra_visits: 1,
+            ..Policy::part1()
+        }
+    }
+
+    fn limit(&self, cave: &Cave) -> Option<u32> {
+        match self.limits.get(&cave.name) {
+            Some(limit) => Some(*limit),
+            None if cave.small => Some(self.small_limit),
+            None => self.big_limit,
+        }
+    }
+}
+
+// A Policy worked out for the caves of one map. Visit counts are only kept
+// for caves with a limit, each in a field just wide enough for that limit.
+struct Limits {
+    start: usize,
+    end: usize,
+    // most visits to each cave and where they're counted, if limited
+    most: Vec<Option<(u32, Field)>>,
+    // whether each cave can take one of the extra visits
+    may_repeat: Vec<bool>,
+    extra_visits: u32,
+    // visits to no caves at all
+    none: Visits,
+}
+
+impl Limits {
+    fn new(map: &Map, policy: &Policy) -> Limits {
+        let (start, end) = (map.find(&policy.start), map.find(&policy.end));
+        let may_repeat = (0..map.caves.len())
+            .map(|a| map.caves[a].small && a != start && a != end)
+            .collect::<Vec<_>>();
+
+        // the start is always visited once, and a cave can go one over its
+        // limit with an extra visit, apart from the last (see enter)
+        let (mut words, mut used) = (0, 0);
+        let most = map
+            .caves
+            .iter()
+            .enumerate()
+            .map(|(a, c)| {
+                let most = policy.limit(c)?;
+                let highest = (most as u64 + (may_repeat[a] && policy.extra_visits > 1) as u64)
+                    .max((a == start) as u64);
+                let bits = u64::BITS - highest.leading_zeros();
+                if words == 0 || used + bits > u64::BITS {
+                    words += 1;
+                    used = 0;
+                }
+                let field = Field {
+                    word: words - 1,
+                    shift: used,
+                    bits,
+                };
+                used += bits;
+                Some((most, field))
+            })
+            .collect::<Vec<_>>();
+
+        Limits {
+            start,
+            end,
+            most,
+            may_repeat,
+            extra_visits: policy.extra_visits,
+            none: Visits::new(words),
+        }
+    }
+
+    // The state after going from state into cave a, if that's allowed.
+    // Once the extra visits are used up it no longer matters which caves
+    // they went to, so counts over the limit go back down to it, keeping
+    // the number of distinct states (and so the memo) small.
+    fn enter(&self, state: &State, a: usize) -> Option<State> {
+        let (most, field) = match self.most[a] {
+            Some(limit) => limit,
+            None => {
+                return Some(State {
+                    cave: a,
+                    ..state.clone()
+                })
             }
-            if a == "start" {
-                // can't revisit start
-                continue;
+        };
+        let visits = state.visits.get(field);
+        let extra =
+            visits == most as u64 && self.may_repeat[a] && state.extra_visits < self.extra_visits;
+        if !extra && visits >= most as u64 {
+            return None;
+        }
+
+        let mut next = State {
+            cave: a,
+            ..state.clone()
+        };
+        if extra {
+            next.extra_visits += 1;
+            if next.extra_visits == self.extra_visits {
+                self.clamp(&mut next.visits);
+                return Some(next);
             }
-            // can we go to cave 'a' in this traversal?
-            let seen = self.visited.contains(a);
-            let is_lower = a.chars().next().unwrap().is_lowercase();
-            // if this is a small cave, have we been here before?
-            if seen && is_lower {
-                if can_visit_twice && !self.visited_small_twice {
-                    // can proceed, but must remember we've double-visited a small cave
-                    paths.push(Path::new(a.to_string(), self.visited.clone(), true));
-                    continue;
-                } else {
-                    // no good
-                    continue;
+        }
+        next.visits.set(field, visits + 1);
+        Some(next)
+    }
+
+    // lowers every count in visits that's over its cave's limit to the limit
+    fn clamp(&self, visits: &mut Visits) {
+        if self.extra_visits > 1 {
+            for &(most, field) in self.most.iter().flatten() {
+                if visits.get(field) > most as u64 {
+                    visits.set(field, most as u64);
                 }
             }
+        }
+    }
+
+    fn start_state(&self) -> State {
+        let mut visits = self.none.clone();
+        if let Some((_, field)) = self.most[self.start] {
+            visits.set(field, 1);
+        }
+        State {
+            cave: self.start,
+            visits,
+            extra_visits: 0,
+        }
+    }
+}
+
+// Where one cave's visits are counted: bits bits from shift in a 64 bit
+// word of Visits. Fields never straddle two words.
+#[derive(Clone, Copy)]
+struct Field {
+    word: usize,
+    shift: u32,
+    bits: u32,
+}
+
+impl Field {
+    fn mask(&self) -> u64 {
+        u64::MAX.checked_shr(u64::BITS - self.bits).unwrap_or(0)
+    }
+}
 
-            if a != "end" {
-                // new path
-                let mut new_visited = self.visited.clone();
-                new_visited.insert(a.to_string());
-                paths.push(Path::new(
-                    a.to_string(),
-                    new_visited,
-                    self.visited_small_twice,
-                ));
+// Visit counts for the caves with a limit. Most maps fit them all in two
+// words, which are kept in one u128 to save an allocation per state.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Visits {
+    Packed(u128),
+    Wide(Box<[u64]>),
+}
+
+impl Visits {
+    fn new(words: usize) -> Visits {
+        if words <= 2 {
+            Visits::Packed(0)
+        } else {
+            Visits::Wide(vec![0; words].into_boxed_slice())
+        }
+    }
+
+    fn get(&self, field: Field) -> u64 {
+        let word = match self {
+            Visits::Packed(v) => (v >> (field.word as u32 * u64::BITS)) as u64,
+            Visits::Wide(v) => v[field.word],
+        };
+        (word >> field.shift) & field.mask()
+    }
+
+    fn set(&mut self, field: Field, count: u64) {
+        let mask = field.mask() << field.shift;
+        let count = (count << field.shift) & mask;
+        match self {
+            Visits::Packed(v) => {
+                let shift = field.word as u32 * u64::BITS;
+                *v = *v & !((mask as u128) << shift) | (count as u128) << shift;
             }
+            Visits::Wide(v) => v[field.word] = v[field.word] & !mask | count,
         }
-        return (found, paths);
     }
 }
 
-fn numpaths(lines: &[&str], can_visit_twice: bool) -> i32 {
-    let mut map: Map = HashMap::new();
-    for l in lines {
-        let names = l.split("-").collect::<Vec<_>>();
-        names.iter().map(|s| s.to_string()).for_each(|cave| {
-            if !map.contains_key(&cave) {
-                map.insert(cave, Cave::new());
+// Everything about a partial path that decides where it can go next.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct State {
+    cave: usize,
+    // times each cave with a limit has been visited, see Limits
+    visits: Visits,
+    // extra visits used
+    extra_visits: u32,
+}
+
+struct Path {
+    // every cave visited so far, in order, starting with start
+    route: Vec<usize>,
+    state: State,
+}
+
+impl Path {
+    fn then(&self, state: State) -> Path {
+        let mut route = self.route.clone();
+        route.push(state.cave);
+        Path { route, state }
+    }
+
+    // returns routes of full paths, new paths to try
+    fn visit(&self, map: &Map, limits: &Limits) -> (Vec<Vec<usize>>, Vec<Path>) {
+        let mut paths = vec![];
+        let mut found = vec![];
+        for &a in &map.caves[self.state.cave].adjacent {
+            if let Some(state) = limits.enter(&self.state, a) {
+                // are we at the end?
+                if a == limits.end {
+                    found.push(self.then(state).route);
+                } else {
+                    paths.push(self.then(state));
+                }
             }
-        });
-        map.get_mut(names[0])
-            .unwrap()
-            .adjacent
-            .push(names[1].to_string());
-        map.get_mut(names[1])
-            .unwrap()
-            .adjacent
-            .push(names[0].to_string());
-    }
-
-    let mut paths = vec![Path::new("start".to_string(), HashSet::new(), false)];
-    let mut found = 0;
-
-    while paths.len() > 0 {
-        let p = paths.pop().unwrap();
-        let (f, np) = p.visit(&map, can_visit_twice);
-        found += f;
+        }
+        (found, paths)
+    }
+}
+
+fn parse(lines: &[&str]) -> Map {
+    let mut map = Map {
+        caves: vec![],
+        ids: HashMap::new(),
+    };
+    for l in lines {
+        let (a, b) = l.split_once('-').unwrap();
+        let (a, b) = (map.id(a), map.id(b));
+        map.caves[a].adjacent.push(b);
+        map.caves[b].adjacent.push(a);
+    }
+    map
+}
+
+// Calls f with the route of every path from start to end.
+fn walk<F: FnMut(&[usize])>(map: &Map, policy: &Policy, mut f: F) {
+    let limits = Limits::new(map, policy);
+    let start = limits.start_state();
+    let mut paths = vec![Path {
+        route: vec![start.cave],
+        state: start,
+    }];
+
+    while let Some(p) = paths.pop() {
+        let (found, np) = p.visit(map, &limits);
+        for route in found {
+            f(&route);
+        }
         paths.extend(np);
     }
+}
+
+// Counts the paths from state to the end. The count only depends on the
+// state, so it is remembered in memo.
+fn count(map: &Map, limits: &Limits, state: State, memo: &mut HashMap<State, u64>) -> u64 {
+    if state.cave == limits.end {
+        return 1;
+    }
+    if let Some(n) = memo.get(&state) {
+        return *n;
+    }
+
+    let mut ret = 0;
+    for &a in &map.caves[state.cave].adjacent {
+        if let Some(next) = limits.enter(&state, a) {
+            ret += count(map, limits, next, memo);
+        }
+    }
+
+    memo.insert(state, ret);
+    ret
+}
+
+fn numpaths(lines: &[&str], policy: &Policy) -> u64 {
+    let map = parse(lines);
+    let limits = Limits::new(&map, policy);
+    count(&map, &limits, limits.start_state(), &mut HashMap::new())
+}
+
+// Every path written like "start,A,b,A,end", in sorted order, keeping only
+// those that go through the cave through if given, and only the first limit
+// of them if given.
+fn list_paths(
+    lines: &[&str],
+    policy: &Policy,
+    through: Option<&str>,
+    limit: Option<usize>,
+) -> Vec<String> {
+    let map = parse(lines);
+    let mut ret = vec![];
+    walk(&map, policy, |route| {
+        if through.is_none_or(|cave| route.iter().any(|c| map.caves[*c].name == cave)) {
+            ret.push(
+                route
+                    .iter()
+                    .map(|c| map.caves[*c].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+    });
+    ret.sort();
+    ret.truncate(limit.unwrap_or(ret.len()));
+    ret
+}
 
-    return found;
+// How many paths use each passage, keyed by the ids of the caves at either
+// end, lowest first.
+fn edge_usage(map: &Map, policy: &Policy) -> HashMap<(usize, usize), u64> {
+    let mut ret = HashMap::new();
+    walk(map, policy, |route| {
+        for w in route.windows(2) {
+            *ret.entry((w[0].min(w[1]), w[0].max(w[1]))).or_insert(0) += 1;
+        }
+    });
+    ret
+}
+
+// The map as a graphviz graph. Small caves are ellipses and big caves boxes,
+// with start and end drawn double. If usage is given each passage is labelled
+// with its count and drawn thicker the more it is used.
+fn to_dot(map: &Map, policy: &Policy, usage: Option<&HashMap<(usize, usize), u64>>) -> String {
+    let mut ret = String::from("graph caves {\n");
+    for c in &map.caves {
+        let mut attrs = vec![match c.small {
+            true => "shape=ellipse",
+            false => "shape=box, style=filled, fillcolor=lightgrey",
+        }];
+        if c.name == policy.start || c.name == policy.end {
+            attrs.push("peripheries=2, color=blue, fontcolor=blue");
+        }
+        ret += &format!("    \"{}\" [{}];\n", c.name, attrs.join(", "));
+    }
+
+    let most = usage.and_then(|u| u.values().max().copied()).unwrap_or(0);
+    for (a, cave) in map.caves.iter().enumerate() {
+        for &b in &cave.adjacent {
+            if a > b {
+                continue;
+            }
+            ret += &format!("    \"{}\" -- \"{}\"", cave.name, map.caves[b].name);
+            if let Some(usage) = usage {
+                let n = usage.get(&(a, b)).copied().unwrap_or(0);
+                let width = match most {
+                    0 => 1.0,
+                    _ => 1.0 + 4.0 * n as f64 / most as f64,
+                };
+                ret += &format!(" [label=\"{}\", penwidth={:.1}]", n, width);
+            }
+            ret += ";\n";
+        }
+    }
+    ret += "}\n";
+    ret
 }
 
-fn part1(lines: &[&str]) -> i32 {
-    numpaths(lines, false)
+fn part1(lines: &[&str]) -> u64 {
+    numpaths(lines, &Policy::part1())
 }
 
-fn part2(lines: &[&str]) -> i32 {
-    numpaths(lines, true)
+fn part2(lines: &[&str]) -> u64 {
+    numpaths(lines, &Policy::part2())
 }
 
 #[cfg(test)]
@@ -128,8 +494,8 @@ mod tests {
 
     struct TestCase<'a> {
         lines: Vec<&'a str>,
-        part1: i32,
-        part2: i32,
+        part1: u64,
+        part2: u64,
     }
 
     #[test]
@@ -163,4 +529,167 @@ mod tests {
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn many_caves() {
+        // a big hub surrounded by small caves, so a path is the hub with any
+        // ordering of any of the small caves in between visits to it
+        let hub = |n: usize| {
+            let mut lines = vec!["start-HUB".to_string(), "HUB-end".to_string()];
+            lines.extend((0..n).map(|i| format!("HUB-c{}", i)));
+            lines
+        };
+
+        // with 60 big caves each between start and end on the side, for 77
+        // caves in all
+        let mut lines = hub(14);
+        lines.extend((0..60).flat_map(|i| vec![format!("start-B{}", i), format!("B{}-end", i)]));
+        let lines = lines.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+        // sum of 14! / (14 - k)! for k in 0..=14
+        let orderings = (0..=14u64)
+            .map(|k| (14 - k + 1..=14).product::<u64>())
+            .sum::<u64>();
+        assert_eq!(numpaths(&lines, &Policy::part1()), orderings + 60);
+
+        let lines = hub(5);
+        let lines = lines.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+        for policy in [Policy::part1(), Policy::part2()] {
+            assert_eq!(
+                numpaths(&lines, &policy) as usize,
+                list_paths(&lines, &policy, None, None).len()
+            );
+        }
+    }
+
+    #[test]
+    fn wide_visits() {
+        fn str_lines(lines: &[String]) -> Vec<&str> {
+            lines.iter().map(|l| l.as_str()).collect()
+        }
+
+        // big caves have no limit, so take no room however many there are
+        let lines = (0..127)
+            .flat_map(|i| vec![format!("start-B{}", i), format!("B{}-end", i)])
+            .collect::<Vec<_>>();
+        assert_eq!(numpaths(&str_lines(&lines), &Policy::part2()), 127);
+
+        // 132 small caves in a row, too many to count in a u128
+        let mut lines = vec!["start-c0".to_string(), "c129-end".to_string()];
+        lines.extend((1..130).map(|i| format!("c{}-c{}", i - 1, i)));
+        for policy in [Policy::part1(), Policy::part2()] {
+            assert_eq!(numpaths(&str_lines(&lines), &policy), 1);
+        }
+
+        // one cave with a high limit only widens its own count: b can be
+        // visited 0 to 300 times on the way through A, among 20 caves
+        let mut lines = vec!["start-A", "A-b", "A-end"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        lines.extend((0..16).flat_map(|i| vec![format!("start-B{}", i), format!("B{}-end", i)]));
+        let policy = Policy {
+            limits: HashMap::from([("b".to_string(), 300)]),
+            ..Policy::part1()
+        };
+        assert_eq!(numpaths(&str_lines(&lines), &policy), 301 + 16);
+    }
+
+    #[test]
+    fn policies() {
+        let lines = ["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"];
+
+        // two small caves may be visited twice
+        let policy = Policy {
+            extra_visits: 2,
+            ..Policy::part1()
+        };
+        let paths = list_paths(&lines, &policy, None, None);
+        assert_eq!(numpaths(&lines, &policy) as usize, paths.len());
+        assert!(paths.len() > 36);
+        assert!(paths.contains(&"start,A,b,A,b,A,c,A,c,A,end".to_string()));
+
+        // b may be visited three times, but nothing else twice
+        let policy = Policy {
+            limits: HashMap::from([("b".to_string(), 3)]),
+            ..Policy::part1()
+        };
+        let paths = list_paths(&lines, &policy, None, None);
+        assert_eq!(numpaths(&lines, &policy) as usize, paths.len());
+        assert!(paths.contains(&"start,A,b,A,b,A,b,end".to_string()));
+        assert!(paths.iter().all(|p| p.matches('c').count() <= 1));
+
+        // A may be visited at most twice
+        let policy = Policy {
+            big_limit: Some(2),
+            ..Policy::part1()
+        };
+        let paths = list_paths(&lines, &policy, None, None);
+        assert!(paths.iter().all(|p| p.matches('A').count() <= 2));
+        assert_eq!(paths.len(), 8);
+
+        // other names for start and end
+        let renamed = ["in-A", "in-b", "A-c", "A-b", "b-d", "A-out", "b-out"];
+        let policy = Policy {
+            start: "in".to_string(),
+            end: "out".to_string(),
+            ..Policy::part2()
+        };
+        assert_eq!(numpaths(&renamed, &policy), 36);
+
+        // b can be visited anywhere from 0 to 300 times, which needs 9 bits
+        // to count
+        let policy = Policy {
+            limits: HashMap::from([("b".to_string(), 300)]),
+            ..Policy::part1()
+        };
+        assert_eq!(numpaths(&["start-A", "A-b", "A-end"], &policy), 301);
+    }
+
+    #[test]
+    fn dot() {
+        let lines = ["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"];
+        let map = parse(&lines);
+        let policy = Policy::part1();
+
+        let dot = to_dot(&map, &policy, None);
+        assert!(dot.starts_with("graph caves {\n"));
+        assert!(dot.contains("\"start\" [shape=ellipse, peripheries=2"));
+        assert!(dot.contains("\"A\" [shape=box"));
+        assert!(dot.contains("\"c\" [shape=ellipse];"));
+        assert_eq!(dot.matches(" -- ").count(), lines.len());
+        assert!(dot.contains("\"start\" -- \"A\";"));
+
+        // of the 10 paths, 7 start through A and 3 through b, and nothing
+        // goes to d
+        let usage = edge_usage(&map, &policy);
+        assert_eq!(usage[&(map.find("start"), map.find("A"))], 7);
+        assert_eq!(usage[&(map.find("start"), map.find("b"))], 3);
+        assert!(!usage.contains_key(&(map.find("b"), map.find("d"))));
+        let dot = to_dot(&map, &policy, Some(&usage));
+        assert!(dot.contains("\"start\" -- \"A\" [label=\"7\", penwidth=3.8];"));
+        assert!(dot.contains("\"b\" -- \"d\" [label=\"0\", penwidth=1.0];"));
+    }
+
+    #[test]
+    fn paths() {
+        let lines = ["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"];
+        // the paths listed in the puzzle for part 2
+        let theirs = include_str!("theirs.txt").lines().collect::<Vec<_>>();
+        assert_eq!(list_paths(&lines, &Policy::part2(), None, None), theirs);
+
+        assert_eq!(
+            list_paths(&lines, &Policy::part1(), Some("c"), None),
+            vec![
+                "start,A,b,A,c,A,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,b,A,c,A,end",
+            ]
+        );
+        assert_eq!(
+            list_paths(&lines, &Policy::part1(), None, Some(2)),
+            vec!["start,A,b,A,c,A,end", "start,A,b,A,end"]
+        );
+    }
 }
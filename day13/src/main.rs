@@ -1,10 +1,63 @@
 use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io;
+use std::io::Write;
 
+mod ocr;
+mod png;
+
+// Pass --origins <x>,<y> to print every position on the unfolded paper that
+// ends up at x,y once every fold is done. Pass --pbm <prefix> or --png
+// <prefix> to save the paper before each fold and at the end as numbered
+// images, or --gif <file> to save them all as an animation.
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
+
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|a| a == "--origins") {
+        let (x, y) = args
+            .get(i + 1)
+            .and_then(|d| d.split_once(','))
+            .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)))
+            .expect("--origins needs a dot like 3,4");
+        let (paper, folds) = parse(&lines).unwrap();
+        let t = Transform::new(paper.width, paper.height, &folds).unwrap();
+        for (x, y) in t.inverse((x, y)) {
+            println!("{},{}", x, y);
+        }
+        return;
+    }
+    for format in ["pbm", "png"] {
+        let flag = format!("--{}", format);
+        if let Some(i) = args.iter().position(|a| *a == flag) {
+            let prefix = args
+                .get(i + 1)
+                .unwrap_or_else(|| panic!("{} needs a file name prefix", flag));
+            let (mut paper, folds) = parse(&lines).unwrap();
+            states(&mut paper, &folds, |n, paper, line| {
+                let filename = format!("{}-{}.{}", prefix, n, format);
+                let mut out = io::BufWriter::new(fs::File::create(filename)?);
+                match format {
+                    "pbm" => paper.write_pbm(&mut out, line),
+                    _ => paper.write_png(&mut out, line),
+                }
+            })
+            .unwrap();
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--gif") {
+        let filename = args.get(i + 1).expect("--gif needs a file name");
+        let mut out = io::BufWriter::new(fs::File::create(filename).unwrap());
+        let (mut paper, folds) = parse(&lines).unwrap();
+        write_gif(&mut paper, &folds, &mut out).unwrap();
+    }
+
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
 }
@@ -30,51 +83,65 @@ impl Paper {
         self.height = self.height.max(y + 1);
     }
 
-    fn fold(&mut self, f: &str) {
-        let axis = f.split(" ").collect::<Vec<_>>()[2]
-            .split("=")
-            .collect::<Vec<_>>();
-        let coord = axis[1].parse::<i32>().unwrap();
-        match axis[0] {
-            "x" => {
-                let to_move = &self
-                    .dots
-                    .iter()
-                    .copied() // XXX - why is this needed?
-                    .filter(|d| d.0 > coord)
-                    .collect::<Vec<_>>();
-                for d in to_move {
-                    let new_x = coord - (d.0 - coord);
-                    self.dots.remove(&d);
-                    self.dots.insert((new_x, d.1));
-                }
-                self.width = coord;
-            }
-            "y" => {
-                let to_move = &self
-                    .dots
-                    .iter()
-                    .copied() // XXX - why is this needed?
-                    .filter(|d| d.1 > coord)
-                    .collect::<Vec<_>>();
-                for d in to_move {
-                    let new_y = coord - (d.1 - coord);
-                    self.dots.remove(&d);
-                    self.dots.insert((d.0, new_y));
-                }
-                self.height = coord;
-            }
-            _ => panic!("unknown axis {:?}", axis),
+    fn size(&self, axis: Axis) -> i32 {
+        match axis {
+            Axis::X => self.width,
+            Axis::Y => self.height,
         }
     }
 
+    // Folds the part of the paper past the fold line onto the rest. If that
+    // part is bigger, the rest ends up shifted along so that nothing goes
+    // below 0. Dots on the fold line itself, or folds that miss the paper,
+    // are errors and leave the paper as it was.
+    fn fold(&mut self, f: &Fold) -> Result<(), String> {
+        let size = self.size(f.axis);
+        if f.at < 0 || f.at >= size {
+            return Err(format!("{} misses the paper, which is {} across", f, size));
+        }
+        if let Some(d) = self.dots.iter().find(|&&d| f.axis.coord(d) == f.at) {
+            return Err(format!("dot {},{} is on the line of {}", d.0, d.1, f));
+        }
+
+        let folded = f.at.max(size - 1 - f.at);
+        self.dots = self
+            .dots
+            .iter()
+            .map(|&(x, y)| match f.axis {
+                Axis::X => (f.reflect(x, folded), y),
+                Axis::Y => (x, f.reflect(y, folded)),
+            })
+            .collect();
+        match f.axis {
+            Axis::X => self.width = folded,
+            Axis::Y => self.height = folded,
+        }
+        Ok(())
+    }
+
+    // Does every fold at once, moving each dot only the one time.
+    fn fold_all(&mut self, folds: &[Fold]) -> Result<(), String> {
+        let t = Transform::new(self.width, self.height, folds)?;
+        self.dots = self
+            .dots
+            .iter()
+            .map(|&d| {
+                t.apply(d)
+                    .ok_or_else(|| format!("dot {},{} ends up on a fold line", d.0, d.1))
+            })
+            .collect::<Result<_, _>>()?;
+        self.width = t.x.size;
+        self.height = t.y.size;
+        Ok(())
+    }
+
     fn count(&self) -> i32 {
         self.dots.len() as i32
     }
 
     fn print(&self) {
-        (0..self.height as i32).for_each(|y| {
-            (0..self.width as i32).for_each(|x| {
+        (0..self.height).for_each(|y| {
+            (0..self.width).for_each(|x| {
                 print!(
                     "{}",
                     if self.dots.contains(&(x, y)) {
@@ -84,61 +151,374 @@ impl Paper {
                     }
                 )
             });
-            println!("")
+            println!()
         })
     }
+
+    // The paper as one of the colours in PALETTE per dot, row by row, with
+    // the line of fold drawn across it.
+    fn pixels(&self, fold: Option<&Fold>) -> Vec<u8> {
+        let mut ret = vec![PAPER; (self.width * self.height) as usize];
+        for &(x, y) in &self.dots {
+            ret[(y * self.width + x) as usize] = DOT;
+        }
+        if let Some(f) = fold {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    if f.axis.coord((x, y)) == f.at {
+                        ret[(y * self.width + x) as usize] = LINE;
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    // Writes the paper as a plain PBM. There's no colour, so the line of fold
+    // is dashed.
+    fn write_pbm<W: Write>(&self, out: &mut W, fold: Option<&Fold>) -> io::Result<()> {
+        writeln!(out, "P1\n{} {}", self.width, self.height)?;
+        let pixels = self.pixels(fold);
+        for (y, row) in pixels.chunks(self.width.max(1) as usize).enumerate() {
+            let row = row
+                .iter()
+                .enumerate()
+                .map(|(x, p)| match *p {
+                    DOT => "1",
+                    LINE if (x + y) % 2 == 0 => "1",
+                    _ => "0",
+                })
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+
+    fn write_png<W: Write>(&self, out: &mut W, fold: Option<&Fold>) -> io::Result<()> {
+        png::write(
+            out,
+            self.width as u32,
+            self.height as u32,
+            &PALETTE,
+            &self.pixels(fold),
+        )
+    }
 }
 
-fn part1(lines: &[&str]) -> i32 {
-    let mut paper = Paper::new();
-    for l in lines {
-        if l.is_empty() {
-            break;
+const PAPER: u8 = 0;
+const DOT: u8 = 1;
+const LINE: u8 = 2;
+// what's left of the page once the paper has been folded smaller
+const OFF: u8 = 3;
+const PALETTE: [[u8; 3]; 4] = [[250, 250, 235], [20, 20, 20], [220, 30, 30], [90, 90, 90]];
+
+// Calls frame with the number of the state, the paper before each fold and
+// the fold about to happen, then the paper once every fold is done.
+fn states<F>(paper: &mut Paper, folds: &[Fold], mut frame: F) -> io::Result<()>
+where
+    F: FnMut(usize, &Paper, Option<&Fold>) -> io::Result<()>,
+{
+    for (n, f) in folds.iter().enumerate() {
+        frame(n, paper, Some(f))?;
+        paper
+            .fold(f)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+    frame(folds.len(), paper, None)
+}
+
+// Saves every state as a frame of an animated GIF the size of the unfolded
+// paper, a second each.
+fn write_gif<W: Write>(paper: &mut Paper, folds: &[Fold], out: &mut W) -> io::Result<()> {
+    let (width, height) = (paper.width as usize, paper.height as usize);
+    let size = |n: usize| {
+        u16::try_from(n).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is too big for a GIF", n),
+            )
+        })
+    };
+    let (gif_width, gif_height) = (size(width)?, size(height)?);
+    let mut images = vec![];
+    states(paper, folds, |_, paper, fold| {
+        let mut image = vec![OFF; width * height];
+        let pixels = paper.pixels(fold);
+        for (row, pixels) in image
+            .chunks_mut(width)
+            .zip(pixels.chunks(paper.width.max(1) as usize))
+        {
+            row[..pixels.len()].copy_from_slice(pixels);
         }
-        let coords = l
-            .split(",")
-            .map(|n| n.parse().unwrap())
-            .collect::<Vec<i32>>();
+        images.push(image);
+        Ok(())
+    })?;
+    gif::write(out, gif_width, gif_height, &PALETTE, &images, 100)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
 
-        paper.add_dot(coords[0], coords[1]);
+impl Axis {
+    fn coord(self, (x, y): (i32, i32)) -> i32 {
+        match self {
+            Axis::X => x,
+            Axis::Y => y,
+        }
     }
-    //println!("initial:");
-    //paper.print();
-    let folds = lines
-        .iter()
-        .filter(|l| l.starts_with("fold"))
-        .collect::<Vec<_>>();
+}
 
-    paper.fold(folds[0]);
-    //println!("after fold:");
-    //paper.print();
-    return paper.count();
+#[derive(Debug, PartialEq)]
+struct Fold {
+    axis: Axis,
+    at: i32,
 }
 
-fn part2(lines: &[&str]) -> i32 {
-    let mut paper = Paper::new();
-    for l in lines {
-        if l.is_empty() {
-            break;
+impl Fold {
+    // Where c ends up along the folded axis, which is size long afterwards.
+    fn reflect(&self, c: i32, size: i32) -> i32 {
+        if c < self.at {
+            c + size - self.at
+        } else {
+            size - (c - self.at)
+        }
+    }
+}
+
+impl fmt::Display for Fold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let axis = match self.axis {
+            Axis::X => 'x',
+            Axis::Y => 'y',
+        };
+        write!(f, "fold along {}={}", axis, self.at)
+    }
+}
+
+// Part of an axis, from start up to but not including end, that folding
+// moves as one piece. c goes to offset - c if flipped, else c + offset.
+#[derive(Debug)]
+struct Piece {
+    start: i32,
+    end: i32,
+    offset: i32,
+    flipped: bool,
+}
+
+impl Piece {
+    fn image(&self, c: i32) -> i32 {
+        if self.flipped {
+            self.offset - c
+        } else {
+            c + self.offset
+        }
+    }
+
+    // The c in this piece that goes to v, if there is one.
+    fn preimage(&self, v: i32) -> Option<i32> {
+        let c = if self.flipped {
+            self.offset - v
+        } else {
+            v - self.offset
+        };
+        (self.start..self.end).contains(&c).then_some(c)
+    }
+}
+
+// Where every coordinate along one axis ends up after some folds, as pieces
+// in order. Coordinates that land on a fold line aren't in any piece.
+struct AxisMap {
+    // length of the axis after the folds
+    size: i32,
+    pieces: Vec<Piece>,
+}
+
+impl AxisMap {
+    fn new(size: i32) -> AxisMap {
+        AxisMap {
+            size,
+            pieces: vec![Piece {
+                start: 0,
+                end: size,
+                offset: 0,
+                flipped: false,
+            }],
+        }
+    }
+
+    // Follows the current mapping with folding at f.
+    fn fold(&mut self, f: &Fold) -> Result<(), String> {
+        if f.at < 0 || f.at >= self.size {
+            return Err(format!(
+                "{} misses the paper, which is {} across",
+                f, self.size
+            ));
+        }
+        let folded = f.at.max(self.size - 1 - f.at);
+        let mut pieces = vec![];
+        for p in &self.pieces {
+            // split p where its image crosses the fold line, at c; the part
+            // before c stays the same way round if p wasn't flipped
+            let c = if p.flipped {
+                p.offset - f.at
+            } else {
+                f.at - p.offset
+            };
+            let before = Piece {
+                start: p.start,
+                end: p.end.min(c),
+                offset: if p.flipped {
+                    folded + f.at - p.offset
+                } else {
+                    p.offset + folded - f.at
+                },
+                flipped: false,
+            };
+            let after = Piece {
+                start: p.start.max(c + 1),
+                end: p.end,
+                offset: if p.flipped {
+                    p.offset + folded - f.at
+                } else {
+                    folded + f.at - p.offset
+                },
+                flipped: true,
+            };
+            for p in [before, after] {
+                if p.start < p.end {
+                    pieces.push(p);
+                }
+            }
+        }
+        self.pieces = pieces;
+        self.size = folded;
+        Ok(())
+    }
+
+    fn apply(&self, c: i32) -> Option<i32> {
+        let i = self.pieces.partition_point(|p| p.end <= c);
+        self.pieces
+            .get(i)
+            .filter(|p| p.start <= c)
+            .map(|p| p.image(c))
+    }
+
+    fn inverse(&self, v: i32) -> Vec<i32> {
+        self.pieces.iter().filter_map(|p| p.preimage(v)).collect()
+    }
+}
+
+// A list of folds of a width x height paper turned into one mapping from
+// where a dot starts to where it ends up.
+struct Transform {
+    x: AxisMap,
+    y: AxisMap,
+}
+
+impl Transform {
+    fn new(width: i32, height: i32, folds: &[Fold]) -> Result<Transform, String> {
+        let mut t = Transform {
+            x: AxisMap::new(width),
+            y: AxisMap::new(height),
+        };
+        for f in folds {
+            match f.axis {
+                Axis::X => t.x.fold(f)?,
+                Axis::Y => t.y.fold(f)?,
+            }
         }
-        let coords = l
-            .split(",")
-            .map(|n| n.parse().unwrap())
-            .collect::<Vec<i32>>();
+        Ok(t)
+    }
 
-        paper.add_dot(coords[0], coords[1]);
+    // Where the dot ends up, or None if it lands on a fold line or isn't on
+    // the paper.
+    fn apply(&self, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+        Some((self.x.apply(x)?, self.y.apply(y)?))
+    }
+
+    // Every dot that ends up at (x, y).
+    fn inverse(&self, (x, y): (i32, i32)) -> Vec<(i32, i32)> {
+        let ys = self.y.inverse(y);
+        self.x
+            .inverse(x)
+            .into_iter()
+            .flat_map(|x| ys.iter().map(move |&y| (x, y)))
+            .collect()
+    }
+}
+
+// Parses a line like "fold along x=5".
+fn parse_fold(line: &str) -> Result<Fold, String> {
+    let (axis, at) = line
+        .strip_prefix("fold along ")
+        .and_then(|f| f.split_once('='))
+        .ok_or_else(|| format!("expected \"fold along <axis>=<n>\", got {:?}", line))?;
+    let axis = match axis {
+        "x" => Axis::X,
+        "y" => Axis::Y,
+        _ => return Err(format!("unknown axis {:?} in {:?}", axis, line)),
+    };
+    let at = at
+        .parse()
+        .map_err(|e| format!("bad position {:?} in {:?}: {}", at, line, e))?;
+    Ok(Fold { axis, at })
+}
+
+// The dots before the blank line, then the folds after it. The first fold
+// along each axis is taken to be down the middle of the paper, so the paper
+// is at least twice as big as that, even if no dots are near the far edge.
+fn parse(lines: &[&str]) -> Result<(Paper, Vec<Fold>), String> {
+    let mut paper = Paper::new();
+    let mut lines = lines.iter();
+    for l in lines.by_ref().take_while(|l| !l.is_empty()) {
+        let (x, y) = l
+            .split_once(',')
+            .ok_or_else(|| format!("expected \"<x>,<y>\", got {:?}", l))?;
+        let coord = |n: &str| {
+            n.parse::<i32>()
+                .map_err(|e| format!("bad coordinate {:?} in {:?}: {}", n, l, e))
+        };
+        let (x, y) = (coord(x)?, coord(y)?);
+        if x < 0 || y < 0 {
+            return Err(format!("negative coordinate in {:?}", l));
+        }
+        paper.add_dot(x, y);
     }
     let folds = lines
-        .iter()
-        .filter(|l| l.starts_with("fold"))
-        .collect::<Vec<_>>();
+        .map(|l| parse_fold(l))
+        .collect::<Result<Vec<_>, _>>()?;
+    for axis in [Axis::X, Axis::Y] {
+        if let Some(f) = folds.iter().find(|f| f.axis == axis) {
+            let size = paper.size(axis).max(2 * f.at + 1);
+            match axis {
+                Axis::X => paper.width = size,
+                Axis::Y => paper.height = size,
+            }
+        }
+    }
+    Ok((paper, folds))
+}
 
-    for f in folds {
-        paper.fold(f);
+fn part1(lines: &[&str]) -> i32 {
+    let (mut paper, folds) = parse(lines).unwrap();
+    paper.fold(&folds[0]).unwrap();
+    paper.count()
+}
+
+// The code spelled out once every fold is done.
+fn part2(lines: &[&str]) -> String {
+    let (mut paper, folds) = parse(lines).unwrap();
+    paper.fold_all(&folds).unwrap();
+    let code = ocr::read(&paper.dots).unwrap_or_default();
+    if code.is_empty() || code.contains('?') {
+        // let a human have a go
+        println!("after folds:");
+        paper.print();
     }
-    println!("after folds:");
-    paper.print();
-    return 0;
+    code
 }
 
 #[cfg(test)]
@@ -148,7 +528,7 @@ mod tests {
     struct TestCase<'a> {
         lines: Vec<&'a str>,
         part1: i32,
-        part2: i32,
+        part2: &'a str,
     }
 
     #[test]
@@ -178,11 +558,228 @@ mod tests {
                 "fold along x=5",
             ],
             part1: 17,
-            part2: 0,
+            // a square, which isn't a letter
+            part2: "?",
         }];
         for t in cases {
             assert_eq!(part1(&t.lines), t.part1, "part 1 test case {:?}", t.lines);
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn folds() {
+        assert_eq!(
+            parse_fold("fold along y=7"),
+            Ok(Fold {
+                axis: Axis::Y,
+                at: 7
+            })
+        );
+        assert_eq!(
+            parse_fold("fold along x=5").unwrap().to_string(),
+            "fold along x=5"
+        );
+        assert!(parse_fold("fold along z=5").is_err());
+        assert!(parse_fold("fold along x=five").is_err());
+        assert!(parse_fold("fold x=5").is_err());
+        assert!(parse(&["1,2", "3"]).is_err());
+        assert!(parse(&["1,2", "", "fold along y=1", "oops"]).is_err());
+
+        // the left part is smaller, so it moves right to meet the other
+        let (mut paper, _) = parse(&["0,0", "2,1", "9,0", "9,1"]).unwrap();
+        paper.fold(&parse_fold("fold along x=3").unwrap()).unwrap();
+        assert_eq!(paper.width, 6);
+        let dots = [(3, 0), (5, 1), (0, 0), (0, 1)];
+        assert_eq!(paper.dots, dots.iter().copied().collect());
+
+        // the bottom part is smaller, so everything stays where it is
+        let (mut paper, _) = parse(&["0,0", "1,6", "0,5"]).unwrap();
+        paper.fold(&parse_fold("fold along y=4").unwrap()).unwrap();
+        assert_eq!(paper.height, 4);
+        assert_eq!(
+            paper.dots,
+            [(0, 0), (1, 2), (0, 3)].iter().copied().collect()
+        );
+
+        // the paper reaches past the last dot
+        let (mut paper, folds) = parse(&["0,0", "9,1", "", "fold along x=10"]).unwrap();
+        assert_eq!(paper.width, 21);
+        paper.fold(&folds[0]).unwrap();
+        assert_eq!(paper.width, 10);
+        assert_eq!(paper.dots, [(0, 0), (9, 1)].iter().copied().collect());
+
+        let (mut paper, _) = parse(&["0,0", "1,2", "3,4"]).unwrap();
+        assert_eq!(
+            paper.fold(&parse_fold("fold along y=2").unwrap()),
+            Err("dot 1,2 is on the line of fold along y=2".to_string())
+        );
+        assert!(paper.fold(&parse_fold("fold along x=4").unwrap()).is_err());
+        assert_eq!(paper.dots.len(), 3);
+    }
+
+    #[test]
+    fn ocr() {
+        for font in [ocr::SMALL, ocr::LARGE] {
+            for (c, g) in font.glyphs {
+                assert_eq!(g.len() as i32, font.width * font.height, "glyph {}", c);
+            }
+            let letters = font.glyphs.iter().map(|(c, _)| *c).collect::<String>();
+            assert_eq!(ocr::read(&font.draw(&letters)), Some(letters));
+        }
+
+        let paper = [
+            "####.####.#....####...##..##..###..####.",
+            "#....#....#....#.......#.#..#.#..#.#....",
+            "###..###..#....###.....#.#....#..#.###..",
+            "#....#....#....#.......#.#.##.###..#....",
+            "#....#....#....#....#..#.#..#.#.#..#....",
+            "####.#....####.#.....##...###.#..#.#....",
+        ];
+        let mut dots = HashSet::new();
+        for (y, row) in paper.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    dots.insert((x as i32, y as i32));
+                }
+            }
+        }
+        assert_eq!(ocr::read(&dots).unwrap(), "EFLFJGRF");
+
+        // a smudge makes one letter unreadable
+        dots.insert((3, 1));
+        assert_eq!(ocr::read(&dots).unwrap(), "?FLFJGRF");
+        // too tall for any font
+        dots.insert((0, 10));
+        assert_eq!(ocr::read(&dots), None);
+        assert_eq!(ocr::read(&HashSet::new()).unwrap(), "");
+    }
+
+    #[test]
+    fn transform() {
+        let lines = [
+            "6,10",
+            "0,14",
+            "9,10",
+            "0,3",
+            "10,4",
+            "4,11",
+            "6,0",
+            "6,12",
+            "4,1",
+            "0,13",
+            "10,12",
+            "3,4",
+            "3,0",
+            "8,4",
+            "1,10",
+            "2,14",
+            "8,10",
+            "9,0",
+            "",
+            "fold along y=7",
+            "fold along x=5",
+            "fold along y=5",
+        ];
+        let (mut one_by_one, folds) = parse(&lines).unwrap();
+        for f in &folds {
+            one_by_one.fold(f).unwrap();
+        }
+        let (mut paper, _) = parse(&lines).unwrap();
+        paper.fold_all(&folds).unwrap();
+        assert_eq!(paper.dots, one_by_one.dots);
+        assert_eq!(
+            (paper.width, paper.height),
+            (one_by_one.width, one_by_one.height)
+        );
+
+        // folding the left 3 columns onto the right 6
+        let (mut one_by_one, folds) =
+            parse(&["0,0", "2,1", "9,0", "9,1", "", "fold along x=3"]).unwrap();
+        one_by_one.fold(&folds[0]).unwrap();
+        let (mut paper, _) = parse(&["0,0", "2,1", "9,0", "9,1"]).unwrap();
+        paper.fold_all(&folds).unwrap();
+        assert_eq!(paper.dots, one_by_one.dots);
+        assert_eq!(paper.width, 6);
+
+        let (mut paper, _) = parse(&["0,0", "3,2", "1,5"]).unwrap();
+        let folds = [
+            parse_fold("fold along y=3").unwrap(),
+            parse_fold("fold along y=1").unwrap(),
+        ];
+        assert_eq!(
+            paper.fold_all(&folds),
+            Err("dot 1,5 ends up on a fold line".to_string())
+        );
+
+        let t = Transform::new(11, 15, &folds[..0]).unwrap();
+        assert_eq!(t.inverse((3, 4)), vec![(3, 4)]);
+        let (_, folds) = parse(&lines[18..21]).unwrap();
+        let t = Transform::new(11, 15, &folds).unwrap();
+        assert_eq!(t.apply((10, 14)), Some((0, 0)));
+        assert_eq!(t.apply((5, 0)), None);
+        assert_eq!(t.apply((11, 0)), None);
+        let mut corners = t.inverse((0, 0));
+        corners.sort();
+        assert_eq!(corners, vec![(0, 0), (0, 14), (10, 0), (10, 14)]);
+
+        for folds in [&folds[..], &parse(&lines[18..]).unwrap().1] {
+            let t = Transform::new(11, 15, folds).unwrap();
+            let mut mapped = 0;
+            for x in 0..11 {
+                for y in 0..15 {
+                    if let Some(d) = t.apply((x, y)) {
+                        assert!(t.inverse(d).contains(&(x, y)));
+                        mapped += 1;
+                    }
+                }
+            }
+            let mut found = 0;
+            for x in 0..t.x.size {
+                for y in 0..t.y.size {
+                    found += t.inverse((x, y)).len();
+                }
+            }
+            assert_eq!(found, mapped);
+        }
+    }
+
+    #[test]
+    fn render() {
+        let (mut paper, folds) = parse(&["0,0", "4,1", "", "fold along x=2"]).unwrap();
+        assert_eq!(
+            paper.pixels(Some(&folds[0])),
+            vec![DOT, PAPER, LINE, PAPER, PAPER, PAPER, PAPER, LINE, PAPER, DOT]
+        );
+
+        let mut out = vec![];
+        paper.write_pbm(&mut out, Some(&folds[0])).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P1\n5 2\n1 0 1 0 0\n0 0 0 0 1\n"
+        );
+
+        let mut out = vec![];
+        paper.write_png(&mut out, None).unwrap();
+        assert!(out.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x05"));
+        assert!(out.ends_with(b"IEND\xae\x42\x60\x82"));
+
+        let mut seen = vec![];
+        states(&mut paper, &folds, |n, paper, fold| {
+            seen.push((n, paper.width, fold.is_some()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(seen, vec![(0, 5, true), (1, 2, false)]);
+
+        let (mut paper, folds) = parse(&["0,0", "4,1", "", "fold along x=2"]).unwrap();
+        let mut out = vec![];
+        write_gif(&mut paper, &folds, &mut out).unwrap();
+        assert!(out.starts_with(b"GIF89a\x05\x00\x02\x00"));
+        assert_eq!(out.last(), Some(&0x3b));
+
+        let (mut paper, folds) = parse(&["0,0", "70000,1", "", "fold along x=35000"]).unwrap();
+        let err = write_gif(&mut paper, &folds, &mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
 }
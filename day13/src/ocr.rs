@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+
+// A fixed width font of capital letters, each drawn with '#' for a dot and
+// '.' for a gap, one row after another. Letters start every stride columns.
+pub struct Font {
+    pub width: i32,
+    pub height: i32,
+    pub stride: i32,
+    pub glyphs: &'static [(char, &'static str)],
+}
+
+// The 4x6 font used by most puzzles.
+pub const SMALL: Font = Font {
+    width: 4,
+    height: 6,
+    stride: 5,
+    glyphs: &[
+        ('A', ".##.#..##..######..##..#"),
+        ('B', "###.#..####.#..##..####."),
+        ('C', ".##.#..##...#...#..#.##."),
+        ('E', "#####...###.#...#...####"),
+        ('F', "#####...###.#...#...#..."),
+        ('G', ".##.#..##...#.###..#.###"),
+        ('H', "#..##..######..##..##..#"),
+        ('I', ".###..#...#...#...#..###"),
+        ('J', "..##...#...#...##..#.##."),
+        ('K', "#..##.#.##..#.#.#.#.#..#"),
+        ('L', "#...#...#...#...#...####"),
+        ('O', ".##.#..##..##..##..#.##."),
+        ('P', "###.#..##..####.#...#..."),
+        ('R', "###.#..##..####.#.#.#..#"),
+        ('S', ".####...#....##....####."),
+        ('U', "#..##..##..##..##..#.##."),
+        ('Z', "####...#..#..#..#...####"),
+    ],
+};
+
+// The 6x10 font used by some larger puzzles.
+pub const LARGE: Font = Font {
+    width: 6,
+    height: 10,
+    stride: 8,
+    glyphs: &[
+        (
+            'A',
+            "..##...#..#.#....##....##....########....##....##....##....#",
+        ),
+        (
+            'B',
+            "#####.#....##....##....######.#....##....##....##....######.",
+        ),
+        (
+            'C',
+            ".####.#....##.....#.....#.....#.....#.....#.....#....#.####.",
+        ),
+        (
+            'E',
+            "#######.....#.....#.....#####.#.....#.....#.....#.....######",
+        ),
+        (
+            'F',
+            "#######.....#.....#.....#####.#.....#.....#.....#.....#.....",
+        ),
+        (
+            'G',
+            ".####.#....##.....#.....#.....#..####....##....##...##.###.#",
+        ),
+        (
+            'H',
+            "#....##....##....##....########....##....##....##....##....#",
+        ),
+        (
+            'J',
+            "...###....#.....#.....#.....#.....#.....#.#...#.#...#..###..",
+        ),
+        (
+            'K',
+            "#....##...#.#..#..#.#...##....##....#.#...#..#..#...#.#....#",
+        ),
+        (
+            'L',
+            "#.....#.....#.....#.....#.....#.....#.....#.....#.....######",
+        ),
+        (
+            'N',
+            "#....###...###...##.#..##.#..##..#.##..#.##...###...###....#",
+        ),
+        (
+            'P',
+            "#####.#....##....##....######.#.....#.....#.....#.....#.....",
+        ),
+        (
+            'R',
+            "#####.#....##....##....######.#..#..#...#.#...#.#....##....#",
+        ),
+        (
+            'X',
+            "#....##....#.#..#..#..#...##....##...#..#..#..#.#....##....#",
+        ),
+        (
+            'Z',
+            "######.....#.....#....#....#....#....#....#.....#.....######",
+        ),
+    ],
+};
+
+impl Font {
+    // The glyph drawn in the width x height cell whose top left is (x, 0),
+    // or '?' if it isn't a letter in this font.
+    fn glyph(&self, dots: &HashSet<(i32, i32)>, x: i32) -> char {
+        let mut drawn = String::new();
+        for dy in 0..self.height {
+            for dx in 0..self.width {
+                drawn.push(if dots.contains(&(x + dx, dy)) {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+        }
+        self.glyphs
+            .iter()
+            .find(|(_, g)| *g == drawn)
+            .map_or('?', |(c, _)| *c)
+    }
+
+    // The dots that spell out text, for any letters this font has.
+    #[allow(dead_code)]
+    pub fn draw(&self, text: &str) -> HashSet<(i32, i32)> {
+        let mut dots = HashSet::new();
+        for (i, c) in text.chars().enumerate() {
+            let (_, g) = self
+                .glyphs
+                .iter()
+                .find(|(g, _)| *g == c)
+                .unwrap_or_else(|| panic!("no glyph for {}", c));
+            for (j, b) in g.bytes().enumerate() {
+                if b == b'#' {
+                    let j = j as i32;
+                    dots.insert((i as i32 * self.stride + j % self.width, j / self.width));
+                }
+            }
+        }
+        dots
+    }
+}
+
+// Reads the letters spelled out by dots, which start at the top left. Uses
+// the smallest font tall enough to hold them, with '?' for anything it can't
+// recognise. Returns None if the dots are too tall for every font.
+pub fn read(dots: &HashSet<(i32, i32)>) -> Option<String> {
+    let width = dots.iter().map(|d| d.0 + 1).max().unwrap_or(0);
+    let height = dots.iter().map(|d| d.1 + 1).max().unwrap_or(0);
+    let fonts = [SMALL, LARGE];
+    let font = fonts.iter().find(|f| f.height >= height)?;
+    let letters = (width + font.stride - 1) / font.stride;
+    Some(
+        (0..letters)
+            .map(|i| font.glyph(dots, i * font.stride))
+            .collect(),
+    )
+}
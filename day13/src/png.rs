@@ -0,0 +1,83 @@
+use std::io;
+use std::io::Write;
+
+// Writes a PNG where each pixel is an index into palette, which can have at
+// most 256 colours. The image data is stored without compression.
+pub fn write<W: Write>(
+    out: &mut W,
+    width: u32,
+    height: u32,
+    palette: &[[u8; 3]],
+    pixels: &[u8],
+) -> io::Result<()> {
+    assert!(palette.len() <= 256, "at most 256 colours");
+    assert_eq!(pixels.len(), width as usize * height as usize);
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = vec![];
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bits per pixel, indexed colour, default compression, filtering and
+    // no interlacing
+    header.extend([8, 3, 0, 0, 0]);
+    chunk(out, b"IHDR", &header)?;
+    chunk(out, b"PLTE", &palette.concat())?;
+
+    // every row starts with a filter type, 0 for none
+    let mut rows = vec![];
+    for row in pixels.chunks(width.max(1) as usize) {
+        rows.push(0);
+        rows.extend(row);
+    }
+    chunk(out, b"IDAT", &zlib(&rows))?;
+    chunk(out, b"IEND", &[])
+}
+
+fn chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(&[&kind[..], data].concat());
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// A zlib stream holding data in stored (uncompressed) deflate blocks.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no dictionary, fastest
+    let mut ret = vec![0x78, 0x01];
+    let blocks = data.chunks(0xffff).collect::<Vec<_>>();
+    if blocks.is_empty() {
+        ret.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        ret.push((i == blocks.len() - 1) as u8);
+        let len = block.len() as u16;
+        ret.extend(len.to_le_bytes());
+        ret.extend((!len).to_le_bytes());
+        ret.extend(*block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for d in data {
+        a = (a + *d as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    ret.extend(((b << 16) | a).to_be_bytes());
+    ret
+}
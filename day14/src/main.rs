@@ -1,10 +1,67 @@
 use std::collections::HashMap;
+use std::env;
 use std::fs;
 
+// Pass --at <steps> <i> to print the character at index i of the polymer
+// after that many steps, or --prefix <steps> <k> to print its first k
+// characters. Pass --steps <n> to count each element after n steps using
+// matrix powers, along with --modulo <m> to count modulo m (which can't be
+// 0) when the counts are too big for a u128.
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
+
+    let args = env::args().collect::<Vec<_>>();
+    for flag in ["--at", "--prefix"] {
+        if let Some(i) = args.iter().position(|a| a == flag) {
+            let arg = |j: usize| -> u64 {
+                args.get(i + j)
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| panic!("{} needs <steps> and a number", flag))
+            };
+            let (template, rules) = parse(&lines);
+            let e = Expansion::new(&template, &rules, arg(1) as usize);
+            match flag {
+                "--at" => match e.char_at(arg(2)) {
+                    Some(c) => println!("{}", c),
+                    None => println!("the polymer is only {} long", e.len()),
+                },
+                _ => println!("{}", e.prefix(arg(2) as usize)),
+            }
+            return;
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--steps") {
+        let value = |j: usize| -> u64 {
+            args.get(j + 1)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("{} needs a number", args[j]))
+        };
+        let arith = match args.iter().position(|a| a == "--modulo") {
+            Some(j) => match value(j) {
+                0 => panic!("can't count modulo 0"),
+                m => Arith::Modulo(m),
+            },
+            None => Arith::Checked,
+        };
+        let (template, rules) = parse(&lines);
+        match counts_after(&template, &rules, value(i), arith) {
+            Ok(counts) => {
+                for (c, n) in counts.iter().enumerate().filter(|(_, n)| **n > 0) {
+                    println!("{} {}", (b'A' + c as u8) as char, n);
+                }
+                if let Arith::Checked = arith {
+                    let present = counts.iter().filter(|n| **n > 0);
+                    let most = present.clone().max().unwrap();
+                    println!("most - least is {}", most - present.min().unwrap());
+                }
+            }
+            Err(e) => println!("{}, try --modulo", e),
+        }
+        return;
+    }
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
 }
@@ -34,8 +91,8 @@ impl Polymer {
     fn step(&mut self, rules: &Rules) {
         let mut new_pairs = HashMap::new();
         for k in self.pairs.keys() {
-            let count = *self.pairs.get(&k).unwrap();
-            match rules.get(&k) {
+            let count = *self.pairs.get(k).unwrap();
+            match rules.get(k) {
                 Some(insert) => {
                     add_count(&mut new_pairs, (k.0, *insert), count);
                     add_count(&mut new_pairs, (*insert, k.1), count);
@@ -58,7 +115,299 @@ impl Polymer {
     }
 }
 
-fn doit(lines: &[&str], steps: i32) -> i64 {
+// Where a polymer ends up after some steps, found a character at a time
+// without building the whole thing.
+struct Expansion<'a> {
+    template: &'a [char],
+    rules: &'a Rules,
+    steps: usize,
+    // lengths[n] has how many characters each pair with a rule turns into
+    // after n steps, not counting its second character, which belongs to
+    // the next pair. These grow exponentially, so they saturate, and once
+    // a step changes none of them the last entry holds for every step after.
+    lengths: Vec<HashMap<(char, char), u64>>,
+}
+
+impl<'a> Expansion<'a> {
+    fn new(template: &'a [char], rules: &'a Rules, steps: usize) -> Expansion<'a> {
+        let mut lengths = vec![rules.keys().map(|p| (*p, 1)).collect::<HashMap<_, _>>()];
+        for n in 1..=steps {
+            let prev = &lengths[n - 1];
+            let length = |p| -> u64 { *prev.get(&p).unwrap_or(&1) };
+            let next = rules
+                .iter()
+                .map(|(&(a, b), &c)| ((a, b), length((a, c)).saturating_add(length((c, b)))))
+                .collect::<HashMap<_, _>>();
+            if next == *prev {
+                break;
+            }
+            lengths.push(next);
+        }
+        Expansion {
+            template,
+            rules,
+            steps,
+            lengths,
+        }
+    }
+
+    // Pairs without a rule never grow.
+    fn length(&self, pair: (char, char), steps: usize) -> u64 {
+        *self.lengths[steps.min(self.fixed())]
+            .get(&pair)
+            .unwrap_or(&1)
+    }
+
+    // the steps after which lengths stop changing
+    fn fixed(&self) -> usize {
+        self.lengths.len() - 1
+    }
+
+    fn len(&self) -> u64 {
+        self.template.windows(2).fold(1, |len, p| {
+            len.saturating_add(self.length((p[0], p[1]), self.steps))
+        })
+    }
+
+    // Finds which pair of the template i falls in, then repeatedly which
+    // half of that pair's expansion it falls in, until it's down to a pair
+    // that doesn't grow any more.
+    //
+    // Past the fixed point the lengths don't depend on steps, so once a
+    // pair comes round again the same choices keep repeating, taking the
+    // same amount off i each time. Those cycles are skipped for as long as
+    // i stays big enough to go through them.
+    fn char_at(&self, mut i: u64) -> Option<char> {
+        for p in self.template.windows(2) {
+            let (mut pair, mut steps) = ((p[0], p[1]), self.steps);
+            if i >= self.length(pair, steps) {
+                i -= self.length(pair, steps);
+                continue;
+            }
+            // pairs met past the fixed point, with the steps and i then
+            let mut seen = HashMap::new();
+            while steps > 0 {
+                let c = match self.rules.get(&pair) {
+                    Some(c) => *c,
+                    None => break,
+                };
+                if steps > self.fixed() {
+                    if let Some((before, was)) = seen.insert(pair, (steps, i)) {
+                        let (cycle, taken) = (before - steps, was - i);
+                        let cycles = ((steps - self.fixed()) / cycle)
+                            .min(i.checked_div(taken).unwrap_or(u64::MAX) as usize);
+                        steps -= cycles * cycle;
+                        i -= cycles as u64 * taken;
+                        seen.clear();
+                        continue;
+                    }
+                }
+                steps -= 1;
+                let left = self.length((pair.0, c), steps);
+                if i < left {
+                    pair = (pair.0, c);
+                } else {
+                    i -= left;
+                    pair = (c, pair.1);
+                }
+            }
+            return Some(pair.0);
+        }
+        // the only character left is the last one of the template
+        match i {
+            0 => self.template.last().copied(),
+            _ => None,
+        }
+    }
+
+    // The first k characters, or the whole polymer if it's shorter. Expands
+    // pairs depth first, leftmost first, stopping as soon as there are
+    // enough. Past the fixed point that would go down a step at a time, so
+    // each character is found on its own instead.
+    fn prefix(&self, k: usize) -> String {
+        if self.steps > self.fixed() {
+            return (0..k as u64).map_while(|i| self.char_at(i)).collect();
+        }
+        let mut ret = String::new();
+        let mut todo = self
+            .template
+            .windows(2)
+            .rev()
+            .map(|p| ((p[0], p[1]), self.steps))
+            .collect::<Vec<_>>();
+        while ret.len() < k {
+            let (pair, steps) = match todo.pop() {
+                Some(t) => t,
+                None => {
+                    ret.extend(self.template.last());
+                    break;
+                }
+            };
+            match self.rules.get(&pair) {
+                Some(&c) if steps > 0 => {
+                    todo.push(((c, pair.1), steps - 1));
+                    todo.push(((pair.0, c), steps - 1));
+                }
+                _ => ret.push(pair.0),
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Arith {
+    // exact, as long as everything fits in a u128; fails on overflow
+    Checked,
+    // modulo a u64, so that products of two values still fit in a u128;
+    // the modulus must not be 0
+    Modulo(u64),
+}
+
+impl Arith {
+    fn add(self, a: u128, b: u128) -> Option<u128> {
+        match self {
+            Arith::Checked => a.checked_add(b),
+            Arith::Modulo(m) => Some((a + b) % m as u128),
+        }
+    }
+
+    // a and b are both less than the modulus, so this can't overflow
+    fn mul(self, a: u128, b: u128) -> Option<u128> {
+        match self {
+            Arith::Checked => a.checked_mul(b),
+            Arith::Modulo(m) => Some(a * b % m as u128),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Matrix {
+    size: usize,
+    // row by row
+    vals: Vec<u128>,
+}
+
+impl Matrix {
+    fn identity(size: usize) -> Matrix {
+        let mut vals = vec![0; size * size];
+        (0..size).for_each(|i| vals[i * size + i] = 1);
+        Matrix { size, vals }
+    }
+
+    fn mul(&self, other: &Matrix, arith: Arith) -> Option<Matrix> {
+        let n = self.size;
+        let mut vals = vec![0; n * n];
+        for i in 0..n {
+            for k in 0..n {
+                let a = self.vals[i * n + k];
+                if a == 0 {
+                    continue;
+                }
+                for j in 0..n {
+                    let b = other.vals[k * n + j];
+                    if b != 0 {
+                        vals[i * n + j] = arith.add(vals[i * n + j], arith.mul(a, b)?)?;
+                    }
+                }
+            }
+        }
+        Some(Matrix { size: n, vals })
+    }
+
+    // By repeated squaring.
+    fn pow(&self, mut exp: u64, arith: Arith) -> Option<Matrix> {
+        let mut ret = Matrix::identity(self.size);
+        let mut square = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                ret = ret.mul(&square, arith)?;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                square = square.mul(&square, arith)?;
+            }
+        }
+        Some(ret)
+    }
+
+    fn apply(&self, v: &[u128], arith: Arith) -> Option<Vec<u128>> {
+        self.vals
+            .chunks(self.size)
+            .map(|row| {
+                row.iter()
+                    .zip(v)
+                    .try_fold(0, |sum, (a, b)| arith.add(sum, arith.mul(*a, *b)?))
+            })
+            .collect()
+    }
+}
+
+// How many of each element there are after steps steps, or an error if
+// they overflow. Pair counts are a vector that each step multiplies by the same
+// matrix, so all the steps are done at once by multiplying by its power.
+fn counts_after(
+    template: &[char],
+    rules: &Rules,
+    steps: u64,
+    arith: Arith,
+) -> Result<[u128; 26], String> {
+    let overflow = || {
+        format!(
+            "the counts after {} steps don't fit in a u128, which holds at most {}",
+            steps,
+            u128::MAX
+        )
+    };
+    // every pair that can turn up
+    let mut pairs = template
+        .windows(2)
+        .map(|p| (p[0], p[1]))
+        .collect::<Vec<_>>();
+    for (&(a, b), &c) in rules {
+        pairs.extend([(a, b), (a, c), (c, b)]);
+    }
+    pairs.sort_unstable();
+    pairs.dedup();
+    let index = |p: &(char, char)| pairs.binary_search(p).unwrap();
+
+    // entry [to][from] is how many of pair to one pair from turns into
+    let size = pairs.len();
+    let mut step = Matrix {
+        size,
+        vals: vec![0; size * size],
+    };
+    for (from, &(a, b)) in pairs.iter().enumerate() {
+        match rules.get(&(a, b)) {
+            Some(&c) => {
+                step.vals[index(&(a, c)) * size + from] += 1;
+                step.vals[index(&(c, b)) * size + from] += 1;
+            }
+            None => step.vals[from * size + from] += 1,
+        }
+    }
+
+    let mut start = vec![0; size];
+    for p in template.windows(2) {
+        start[index(&(p[0], p[1]))] += 1;
+    }
+    let end = step
+        .pow(steps, arith)
+        .and_then(|m| m.apply(&start, arith))
+        .ok_or_else(overflow)?;
+
+    // every element is the first of some pair, apart from the very last one
+    let mut counts = [0; 26];
+    for (p, n) in pairs.iter().zip(end) {
+        counts[c_idx(p.0)] = arith.add(counts[c_idx(p.0)], n).ok_or_else(overflow)?;
+    }
+    if let Some(&last) = template.last() {
+        counts[c_idx(last)] = arith.add(counts[c_idx(last)], 1).ok_or_else(overflow)?;
+    }
+    Ok(counts)
+}
+
+fn parse(lines: &[&str]) -> (Vec<char>, Rules) {
     let template = lines[0].chars().collect::<Vec<_>>();
 
     let mut rules = HashMap::new();
@@ -71,6 +420,12 @@ fn doit(lines: &[&str], steps: i32) -> i64 {
         rules.insert((pair[0], pair[1]), insert[0]);
     }
 
+    (template, rules)
+}
+
+fn doit(lines: &[&str], steps: i32) -> i64 {
+    let (template, rules) = parse(lines);
+
     let mut polymer = Polymer::default();
 
     template
@@ -82,7 +437,7 @@ fn doit(lines: &[&str], steps: i32) -> i64 {
 
     (0..steps).for_each(|_| polymer.step(&rules));
 
-    return polymer.most() - polymer.least();
+    polymer.most() - polymer.least()
 }
 
 fn part1(lines: &[&str]) -> i32 {
@@ -119,4 +474,151 @@ mod tests {
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn expansion() {
+        let lines = [
+            "NNCB", "", "CH -> B", "HH -> N", "CB -> H", "NH -> C", "HB -> C", "HC -> B",
+            "HN -> C", "NN -> C", "BH -> H", "NC -> B", "NB -> B", "BN -> B", "BB -> N", "BC -> B",
+            "CC -> N", "CN -> C",
+        ];
+        let (template, rules) = parse(&lines);
+        let polymers = [
+            "NNCB",
+            "NCNBCHB",
+            "NBCCNBBBCBHCB",
+            "NBBBCNCCNBBNBNBBCHBHHBCHB",
+            "NBBNBNBBCCNBCNCCNBBNBBNBBBNBBNBBCBHCBHHNHCBBCBHCB",
+        ];
+        for (steps, polymer) in polymers.iter().enumerate() {
+            let e = Expansion::new(&template, &rules, steps);
+            assert_eq!(e.len(), polymer.len() as u64);
+            assert_eq!(e.prefix(100), *polymer);
+            assert_eq!(e.prefix(4), polymer[..4]);
+            let chars = (0..e.len())
+                .map(|i| e.char_at(i).unwrap())
+                .collect::<String>();
+            assert_eq!(chars, *polymer);
+            assert_eq!(e.char_at(e.len()), None);
+        }
+
+        let e = Expansion::new(&template, &rules, 10);
+        assert_eq!(e.len(), 3073);
+        let e = Expansion::new(&template, &rules, 40);
+        assert_eq!(e.len(), 3 * (1 << 40) + 1);
+        let prefix = e.prefix(1000);
+        assert_eq!(prefix.len(), 1000);
+        for (i, c) in prefix.chars().enumerate() {
+            assert_eq!(e.char_at(i as u64), Some(c));
+        }
+        assert_eq!(e.char_at(e.len() - 1), Some('B'));
+
+        // lengths saturate rather than overflowing
+        let e = Expansion::new(&template, &rules, 100);
+        assert_eq!(e.len(), u64::MAX);
+        assert_eq!(
+            e.prefix(4),
+            Expansion::new(&template, &rules, 99).prefix(4)[..4]
+        );
+
+        // and stop being worked out once they stop changing
+        let e = Expansion::new(&template, &rules, 1_000_000_000);
+        assert!(e.fixed() < 100);
+        assert_eq!(e.prefix(4), "NBBN");
+    }
+
+    #[test]
+    fn fast_forward() {
+        // char_at going down one step at a time
+        fn slow_char_at(e: &Expansion, mut i: u64) -> Option<char> {
+            for p in e.template.windows(2) {
+                let (mut pair, mut steps) = ((p[0], p[1]), e.steps);
+                if i >= e.length(pair, steps) {
+                    i -= e.length(pair, steps);
+                    continue;
+                }
+                while let (Some(&c), true) = (e.rules.get(&pair), steps > 0) {
+                    steps -= 1;
+                    let left = e.length((pair.0, c), steps);
+                    if i < left {
+                        pair = (pair.0, c);
+                    } else {
+                        i -= left;
+                        pair = (c, pair.1);
+                    }
+                }
+                return Some(pair.0);
+            }
+            None
+        }
+
+        // AB -> AC CB -> CE EB, CE -> CA AE -> AB BE, where AC and CA never
+        // grow, so going down from AB can take 1 off i twice and be back at
+        // AB four steps later
+        let lines = [
+            "AB", "", "AB -> C", "CB -> E", "CE -> A", "AE -> B", "EB -> E", "EE -> E", "BE -> E",
+        ];
+        let (template, rules) = parse(&lines);
+        let e = Expansion::new(&template, &rules, 300);
+        assert!(e.fixed() < 100);
+        for i in (0..200).chain(1_000_000..1_000_010) {
+            assert_eq!(e.char_at(i), slow_char_at(&e, i));
+        }
+        assert_eq!(e.prefix(200).len(), 200);
+
+        let e = Expansion::new(&template, &rules, 1_000_000_000);
+        assert_eq!(e.len(), u64::MAX);
+        for i in [0, 1, 2, 1_000_000, u64::MAX / 3] {
+            assert!(e.char_at(i).is_some());
+        }
+    }
+
+    #[test]
+    fn matrix() {
+        let lines = [
+            "NNCB", "", "CH -> B", "HH -> N", "CB -> H", "NH -> C", "HB -> C", "HC -> B",
+            "HN -> C", "NN -> C", "BH -> H", "NC -> B", "NB -> B", "BN -> B", "BB -> N", "BC -> B",
+            "CC -> N", "CN -> C",
+        ];
+        let (template, rules) = parse(&lines);
+        let counts = counts_after(&template, &rules, 10, Arith::Checked).unwrap();
+        assert_eq!(counts[c_idx('B')], 1749);
+        assert_eq!(counts[c_idx('C')], 298);
+        assert_eq!(counts[c_idx('H')], 161);
+        assert_eq!(counts[c_idx('N')], 865);
+        let counts = counts_after(&template, &rules, 40, Arith::Checked).unwrap();
+        assert_eq!(counts[c_idx('B')], 2192039569602);
+        assert_eq!(counts[c_idx('H')], 3849876073);
+
+        let m = 1_000_000_007;
+        let modulo = counts_after(&template, &rules, 40, Arith::Modulo(m)).unwrap();
+        for (exact, modulo) in counts.iter().zip(modulo.iter()) {
+            assert_eq!(exact % m as u128, *modulo);
+        }
+
+        // the polymer is 3 * 2^steps + 1 long
+        assert_eq!(
+            counts_after(&template, &rules, 200, Arith::Checked),
+            Err(format!(
+                "the counts after 200 steps don't fit in a u128, which holds at most {}",
+                u128::MAX
+            ))
+        );
+        let steps = 1_000_000_000_000;
+        let counts = counts_after(&template, &rules, steps, Arith::Modulo(m)).unwrap();
+        let two = Matrix {
+            size: 1,
+            vals: vec![2],
+        };
+        let power = two.pow(steps, Arith::Modulo(m)).unwrap().vals[0];
+        assert_eq!(
+            counts.iter().sum::<u128>() % m as u128,
+            (3 * power + 1) % m as u128
+        );
+
+        // a pair without a rule stays put
+        let (template, rules) = parse(&["AB", "", "BA -> C"]);
+        let counts = counts_after(&template, &rules, 5, Arith::Checked).unwrap();
+        assert_eq!((counts[0], counts[1], counts[2]), (1, 1, 0));
+    }
 }
@@ -1,109 +1,143 @@
-use std::cmp::Reverse;
-use std::collections::BinaryHeap;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
 use std::fs;
+use std::io;
+use std::io::Write;
 
+mod path;
+
+use path::{Path, Pos, Search};
+
+// Pass --heat-map <file> to save how far every cell is from the top left as
+// a PGM image, with --tiles <rows>x<cols> to tile the map first (1x1 if not
+// given).
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
+
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|a| a == "--heat-map") {
+        let filename = args.get(i + 1).expect("--heat-map needs a file name");
+        let tiles = match args.iter().position(|a| a == "--tiles") {
+            Some(j) => args
+                .get(j + 1)
+                .and_then(|t| t.split_once('x'))
+                .and_then(|(r, c)| Some((r.parse().ok()?, c.parse().ok()?)))
+                .expect("--tiles needs a size like 5x5"),
+            None => (1, 1),
+        };
+        let grid = Grid::parse(&lines, tiles);
+        grid.write_heat_map(filename, &[(0, 0)]).unwrap();
+    }
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
 }
 
 struct Grid {
-    risks: HashMap<(i32, i32), i32>,
-    expanded: bool,
+    // risk of every cell, including the tiled copies, row by row
+    risks: Vec<u8>,
     height: i32,
     width: i32,
 }
 
 impl Grid {
-    fn parse(lines: &[&str], expanded: bool) -> Grid {
-        let mut risks = HashMap::new();
-
-        for r in 0..lines.len() {
-            let chars = lines[r].chars().collect::<Vec<_>>();
-            for c in 0..lines[r].len() {
-                risks.insert((r as i32, c as i32), (chars[c] as u8 - '0' as u8) as i32);
+    // Parses the input and tiles it tiles.0 times down and tiles.1 times
+    // across, adding 1 to the risk (wrapping from 9 back to 1) for every
+    // step away from the top left copy.
+    fn parse(lines: &[&str], tiles: (i32, i32)) -> Grid {
+        let tile_height = lines.len() as i32;
+        let tile_width = lines[0].len() as i32;
+        let height = tile_height * tiles.0;
+        let width = tile_width * tiles.1;
+
+        let mut risks = Vec::with_capacity((height * width) as usize);
+        for r in 0..height {
+            let line = lines[(r % tile_height) as usize].as_bytes();
+            for c in 0..width {
+                let val = (line[(c % tile_width) as usize] - b'0') as i32;
+                let shift = r / tile_height + c / tile_width;
+                risks.push(((val + shift - 1) % 9 + 1) as u8);
             }
         }
 
-        let height = lines.len() as i32 * if expanded { 5 } else { 1 };
-        let width = lines[0].len() as i32 * if expanded { 5 } else { 1 };
-
-        return Grid {
+        Grid {
             risks,
-            expanded,
             height,
             width,
-        };
+        }
     }
 
     fn risk_at(&self, r: i32, c: i32) -> i32 {
-        let p = match self.expanded {
-            true => (r % (self.height / 5), c % (self.width / 5)),
-            false => (r, c),
-        };
-        let val = *self.risks.get(&p).unwrap();
-        return match self.expanded {
-            true => (val + r / (self.height / 5) + c / (self.width / 5) - 1) % 9 + 1,
-            false => val,
-        };
+        self.risks[(r * self.width + c) as usize] as i32
     }
 
-    fn least_risk(&self) -> i32 {
-        let mut min_risks = HashMap::new();
-        let mut heap = BinaryHeap::new();
+    fn path(&self, start: Pos, goal: Pos, search: Search) -> Option<Path> {
+        path::shortest_path(self.height, self.width, start, goal, search, |p| {
+            self.risk_at(p.0, p.1)
+        })
+    }
 
-        heap.push(Reverse((0, 0, 0)));
+    // cost of the cheapest route from the nearest of sources to every cell,
+    // row by row
+    fn distance_map(&self, sources: &[Pos]) -> Vec<i32> {
+        path::distances(self.height, self.width, sources, |p| self.risk_at(p.0, p.1))
+    }
 
-        while let Some(Reverse((risk, r, c))) = heap.pop() {
-            if r + 1 == self.height && c + 1 == self.width {
-                return risk;
-            }
+    // Writes the distances from sources as a binary PGM image where brighter
+    // cells are further away. Unreachable cells are black.
+    fn write_heat_map(&self, filename: &str, sources: &[Pos]) -> io::Result<()> {
+        let dist = self.distance_map(sources);
+        let max = dist
+            .iter()
+            .filter(|d| **d != i32::MAX)
+            .max()
+            .map_or(1, |m| (*m).max(1)) as i64;
+
+        let mut out = io::BufWriter::new(fs::File::create(filename)?);
+        write!(out, "P5\n{} {}\n255\n", self.width, self.height)?;
+        let pixels = dist
+            .iter()
+            .map(|d| match *d {
+                i32::MAX => 0,
+                d => (d as i64 * 255 / max) as u8,
+            })
+            .collect::<Vec<_>>();
+        out.write_all(&pixels)?;
+        out.flush()
+    }
 
-            [(-1, 0), (1, 0), (0, -1), (0, 1)]
-                .iter()
-                .map(|d| (r as i32 + d.0, c as i32 + d.1))
-                .filter(|p| (0..self.height as i32).contains(&p.0))
-                .filter(|p| (0..self.width as i32).contains(&p.1))
-                .for_each(|p| {
-                    let new_risk = risk + self.risk_at(p.0, p.1);
-
-                    let prev_risk = match min_risks.get(&p) {
-                        Some(r) => *r,
-                        None => i32::MAX,
-                    };
-
-                    if new_risk < prev_risk {
-                        min_risks.insert(p, new_risk);
-                        heap.push(Reverse((new_risk, p.0, p.1)));
-                    }
-                });
-        }
-        panic!("did not find");
+    fn least_risk(&self) -> i32 {
+        self.path((0, 0), (self.height - 1, self.width - 1), Search::AStar)
+            .expect("did not find")
+            .cost
     }
 
+    // Prints the risk levels, highlighting the cells on route if given.
     #[allow(dead_code)]
-    fn print(&self) {
+    fn print(&self, route: Option<&Path>) {
+        let on_route = route.map_or(HashSet::new(), |p| p.cells.iter().collect());
         (0..self.height).for_each(|r| {
-            (0..self.width).for_each(|c| print!("{}", self.risk_at(r, c)));
-            println!("");
+            (0..self.width).for_each(|c| match on_route.contains(&(r, c)) {
+                true => print!("\x1b[1;31m{}\x1b[0m", self.risk_at(r, c)),
+                false => print!("{}", self.risk_at(r, c)),
+            });
+            println!();
         })
     }
 }
 
 fn part1(lines: &[&str]) -> i32 {
-    let grid = Grid::parse(lines, false);
+    let grid = Grid::parse(lines, (1, 1));
 
-    return grid.least_risk();
+    grid.least_risk()
 }
 
 fn part2(lines: &[&str]) -> i32 {
-    let grid = Grid::parse(lines, true);
+    let grid = Grid::parse(lines, (5, 5));
 
-    return grid.least_risk();
+    grid.least_risk()
 }
 
 #[cfg(test)]
@@ -146,4 +180,66 @@ mod tests {
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn paths() {
+        let grid = Grid::parse(&["1163", "1381", "2136", "3694"], (1, 1));
+        for search in [Search::Dijkstra, Search::AStar, Search::Dial] {
+            let p = grid.path((3, 0), (0, 3), search).unwrap();
+            assert_eq!(p.cost, 14, "{:?}", search);
+            assert_eq!(p.cells.first(), Some(&(3, 0)));
+            assert_eq!(p.cells.last(), Some(&(0, 3)));
+            assert_eq!(
+                p.cells[1..]
+                    .iter()
+                    .map(|c| grid.risk_at(c.0, c.1))
+                    .sum::<i32>(),
+                p.cost
+            );
+            for w in p.cells.windows(2) {
+                assert_eq!((w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn distances() {
+        let grid = Grid::parse(&["1163", "1381", "2136", "3694"], (1, 1));
+        let dist = grid.distance_map(&[(0, 0)]);
+        assert_eq!(dist[0], 0);
+        assert_eq!(dist[15], grid.least_risk());
+
+        let dist = grid.distance_map(&[(0, 0), (3, 3)]);
+        assert_eq!(
+            dist,
+            vec![0, 1, 7, 10, 1, 4, 12, 7, 3, 4, 7, 6, 6, 10, 9, 0]
+        );
+
+        let filename = std::env::temp_dir().join("day15_heat_map_test.pgm");
+        let filename = filename.to_str().unwrap();
+        grid.write_heat_map(filename, &[(0, 0)]).unwrap();
+        let image = fs::read(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+        assert!(image.starts_with(b"P5\n4 4\n255\n"));
+        assert_eq!(image.len(), b"P5\n4 4\n255\n".len() + 16);
+        assert_eq!(image[image.len() - 1], 255);
+    }
+
+    #[test]
+    fn tiles() {
+        let lines = ["8"];
+        let grid = Grid::parse(&lines, (2, 3));
+        assert_eq!((grid.height, grid.width), (2, 3));
+        assert_eq!(grid.risks, vec![8, 9, 1, 9, 1, 2]);
+
+        let lines = ["1163", "1381", "2136", "3694"];
+        let grid = Grid::parse(&lines, (50, 20));
+        let goal = (grid.height - 1, grid.width - 1);
+        let costs = [Search::Dijkstra, Search::AStar, Search::Dial]
+            .iter()
+            .map(|s| grid.path((0, 0), goal, *s).unwrap().cost)
+            .collect::<Vec<_>>();
+        assert_eq!(costs[0], costs[1]);
+        assert_eq!(costs[0], costs[2]);
+    }
 }
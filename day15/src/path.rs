@@ -0,0 +1,179 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// (row, column)
+pub type Pos = (i32, i32);
+
+#[derive(Debug)]
+pub struct Path {
+    // sum of the costs of every cell entered, so the start cell is free
+    pub cost: i32,
+    // every cell on the route, from start to goal inclusive
+    pub cells: Vec<Pos>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Search {
+    #[allow(dead_code)]
+    Dijkstra,
+    // A* with a Manhattan distance heuristic
+    AStar,
+    // Dijkstra with a bucket per distance instead of a heap (Dial's
+    // algorithm), which is fast when costs are small integers
+    #[allow(dead_code)]
+    Dial,
+}
+
+// Finds the cheapest route from start to goal on a height x width grid,
+// moving up, down, left or right, where cost gives the cost of entering a
+// cell. Every cell must cost at least 1. Returns None if goal can't be
+// reached.
+pub fn shortest_path<F>(
+    height: i32,
+    width: i32,
+    start: Pos,
+    goal: Pos,
+    search: Search,
+    cost: F,
+) -> Option<Path>
+where
+    F: Fn(Pos) -> i32,
+{
+    let index = |p: Pos| (p.0 * width + p.1) as usize;
+
+    let mut dist = vec![i32::MAX; (height * width) as usize];
+    dist[index(start)] = 0;
+
+    let found = match search {
+        Search::Dijkstra => with_heap(height, width, &[start], Some(goal), &cost, &mut dist, |_| 0),
+        Search::AStar => with_heap(height, width, &[start], Some(goal), &cost, &mut dist, |p| {
+            (p.0 - goal.0).abs() + (p.1 - goal.1).abs()
+        }),
+        Search::Dial => with_buckets(height, width, &[start], Some(goal), &cost, &mut dist),
+    };
+    if !found {
+        return None;
+    }
+
+    // Every cell with a known distance can be reached for that cost, so walk
+    // back from the goal through neighbours that account for the difference.
+    let mut cells = vec![goal];
+    let mut p = goal;
+    while p != start {
+        p = neighbors(height, width, p)
+            .find(|n| dist[index(*n)] != i32::MAX && dist[index(*n)] + cost(p) == dist[index(p)])
+            .unwrap();
+        cells.push(p);
+    }
+    cells.reverse();
+
+    Some(Path {
+        cost: dist[index(goal)],
+        cells,
+    })
+}
+
+// Returns the cost of the cheapest route from any of sources to every cell,
+// indexed by row * width + column, with i32::MAX for unreachable cells.
+#[allow(dead_code)]
+pub fn distances<F>(height: i32, width: i32, sources: &[Pos], cost: F) -> Vec<i32>
+where
+    F: Fn(Pos) -> i32,
+{
+    let mut dist = vec![i32::MAX; (height * width) as usize];
+    for p in sources {
+        dist[(p.0 * width + p.1) as usize] = 0;
+    }
+    with_heap(height, width, sources, None, cost, &mut dist, |_| 0);
+    dist
+}
+
+// Runs Dijkstra, or A* if heuristic is nonzero, from sources, filling in dist
+// until goal is reached, or for every cell if there is no goal.
+fn with_heap<F, H>(
+    height: i32,
+    width: i32,
+    sources: &[Pos],
+    goal: Option<Pos>,
+    cost: F,
+    dist: &mut [i32],
+    heuristic: H,
+) -> bool
+where
+    F: Fn(Pos) -> i32,
+    H: Fn(Pos) -> i32,
+{
+    let index = |p: Pos| (p.0 * width + p.1) as usize;
+    let mut heap = BinaryHeap::new();
+
+    for p in sources {
+        heap.push(Reverse((heuristic(*p), 0, *p)));
+    }
+
+    while let Some(Reverse((_, risk, p))) = heap.pop() {
+        if risk > dist[index(p)] {
+            continue;
+        }
+        if Some(p) == goal {
+            return true;
+        }
+
+        for n in neighbors(height, width, p) {
+            let new_risk = risk + cost(n);
+            if new_risk < dist[index(n)] {
+                dist[index(n)] = new_risk;
+                heap.push(Reverse((new_risk + heuristic(n), new_risk, n)));
+            }
+        }
+    }
+    goal.is_none()
+}
+
+// Like with_heap, but keeps a list of cells for every distance seen so far.
+fn with_buckets<F>(
+    height: i32,
+    width: i32,
+    sources: &[Pos],
+    goal: Option<Pos>,
+    cost: F,
+    dist: &mut [i32],
+) -> bool
+where
+    F: Fn(Pos) -> i32,
+{
+    let index = |p: Pos| (p.0 * width + p.1) as usize;
+    let mut buckets = vec![sources.to_vec()];
+
+    let mut risk = 0;
+    while risk < buckets.len() {
+        for p in std::mem::take(&mut buckets[risk]) {
+            if risk as i32 > dist[index(p)] {
+                continue;
+            }
+            if Some(p) == goal {
+                return true;
+            }
+
+            for n in neighbors(height, width, p) {
+                let new_risk = risk as i32 + cost(n);
+                if new_risk < dist[index(n)] {
+                    dist[index(n)] = new_risk;
+                    if buckets.len() <= new_risk as usize {
+                        buckets.resize(new_risk as usize + 1, vec![]);
+                    }
+                    buckets[new_risk as usize].push(n);
+                }
+            }
+        }
+        risk += 1;
+    }
+    goal.is_none()
+}
+
+pub fn neighbors(height: i32, width: i32, p: Pos) -> impl Iterator<Item = Pos> {
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .map(move |d| (p.0 + d.0, p.1 + d.1))
+        .filter(move |p| (0..height).contains(&p.0))
+        .filter(move |p| (0..width).contains(&p.1))
+}
@@ -1,55 +1,361 @@
-use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::Write;
 
+// Pass --steps <n> to print how many pixels are lit after n steps, jumping
+// ahead once the image repeats so that n can be huge. Add --pbm <prefix> or
+// --pgm <prefix> to also save the image before the first step and after
+// each one as numbered files.
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
+
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|a| a == "--steps") {
+        let steps = args
+            .get(i + 1)
+            .and_then(|n| n.parse().ok())
+            .expect("--steps needs a number");
+        let filter = lines[0].chars().map(to_bit).collect::<Vec<_>>();
+        let rule = Rule::trench_map(&filter);
+        for format in ["pbm", "pgm"] {
+            let flag = format!("--{}", format);
+            if let Some(j) = args.iter().position(|a| *a == flag) {
+                let prefix = args
+                    .get(j + 1)
+                    .unwrap_or_else(|| panic!("{} needs a file name prefix", flag));
+                let mut image = Image::parse(&lines[2..]);
+                for step in 0..=steps {
+                    if step > 0 {
+                        image.enhance(&rule, 1);
+                    }
+                    let filename = format!("{}-{}.{}", prefix, step, format);
+                    let mut out = io::BufWriter::new(fs::File::create(filename).unwrap());
+                    match format {
+                        "pbm" => image.write_pbm(&mut out),
+                        _ => image.write_pgm(&mut out),
+                    }
+                    .unwrap();
+                }
+            }
+        }
+        let mut image = Image::parse(&lines[2..]);
+        println!(
+            "{} lit after {} steps",
+            lit_after(&mut image, &rule, steps),
+            steps
+        );
+        return;
+    }
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
 }
 
-#[derive(Default)]
+// A dense image on an infinite background. Pixels are always addressed as
+// (row, column), rows counting down and columns across, matching the order
+// of the input text. Pixels are kept in a bitset with
+// room around them for the image to grow as it is enhanced, and a spare
+// buffer of the same size is used for the output of each step.
+#[derive(Clone, Default)]
 struct Image {
-    known: HashSet<(i32, i32)>,
-    width: i32,
-    height: i32,
-    surround: i32,
+    // stride words per row, rows rows
+    bits: Vec<u64>,
+    spare: Vec<u64>,
+    stride: usize,
+    rows: usize,
+
+    // the known part of the image within bits
+    top: usize,
+    left: usize,
+    height: usize,
+    width: usize,
+
+    // the value of every pixel outside the known part
     outside: bool,
 }
 
 impl Image {
-    fn contains(&self, p: (i32, i32)) -> bool {
-        let last_surround = self.surround - 2;
-        let x_range = -last_surround..self.width as i32 + last_surround;
-        let y_range = -last_surround..self.height as i32 + last_surround;
-        if x_range.contains(&p.0) && y_range.contains(&p.1) {
-            self.known.contains(&p)
-        } else {
-            self.outside
+    // Parses rows of '#' (lit) and '.' (dark) on a dark background.
+    fn parse(lines: &[&str]) -> Image {
+        let mut image = Image {
+            height: lines.len(),
+            width: lines[0].len(),
+            ..Image::default()
+        };
+        image.reserve(0);
+        for (r, l) in lines.iter().enumerate() {
+            assert_eq!(l.len(), image.width, "rows must all be the same width");
+            for (c, ch) in l.chars().enumerate() {
+                image.set(image.top + r, image.left + c, to_bit(ch));
+            }
+        }
+        image
+    }
+
+    // pixel at (r, c) within the known part
+    fn pixel(&self, r: usize, c: usize) -> bool {
+        self.get(self.top + r, self.left + c)
+    }
+
+    // pixel at (r, c) within bits
+    fn get(&self, r: usize, c: usize) -> bool {
+        self.bits[r * self.stride + c / 64] >> (c % 64) & 1 != 0
+    }
+
+    fn set(&mut self, r: usize, c: usize, v: bool) {
+        set_bit(&mut self.bits[r * self.stride..], c, v);
+    }
+
+    // Makes sure there are margin pixels of room on every side of the known
+    // part.
+    fn reserve(&mut self, margin: usize) {
+        let cols = self.stride * 64;
+        if self.top >= margin
+            && self.left >= margin
+            && self.rows >= self.top + self.height + margin
+            && cols >= self.left + self.width + margin
+        {
+            return;
+        }
+
+        // when growing a step at a time, leave room for plenty more steps
+        let margin = margin
+            .max(self.top)
+            .max(self.left)
+            .max(self.height.max(self.width) / 2);
+        let mut grown = Image {
+            stride: (self.width + 2 * margin).div_ceil(64),
+            rows: self.height + 2 * margin,
+            top: margin,
+            left: margin,
+            height: self.height,
+            width: self.width,
+            outside: self.outside,
+            ..Image::default()
+        };
+        grown.bits = vec![0; grown.stride * grown.rows];
+        grown.spare = grown.bits.clone();
+        if !self.bits.is_empty() {
+            for r in 0..self.height {
+                for c in 0..self.width {
+                    let v = self.get(self.top + r, self.left + c);
+                    grown.set(grown.top + r, grown.left + c, v);
+                }
+            }
+        }
+        *self = grown;
+    }
+
+    // Sets the ring of pixels width wide around the known part to the
+    // background, so that enhancing can read them instead of checking bounds.
+    fn fill_border(&mut self, width: usize) {
+        let outside = self.outside;
+        let (top, bottom) = (self.top - width, self.top + self.height + width);
+        let (left, right) = (self.left - width, self.left + self.width + width);
+        for r in top..bottom {
+            let row = &mut self.bits[r * self.stride..(r + 1) * self.stride];
+            if r < self.top || r >= self.top + self.height {
+                for c in left..right {
+                    set_bit(row, c, outside);
+                }
+            } else {
+                for c in (left..self.left).chain(self.left + self.width..right) {
+                    set_bit(row, c, outside);
+                }
+            }
+        }
+    }
+
+    // value of column c of rows r-1, r and r+1 as the low bit of each of
+    // three octal digits
+    fn column(&self, r: usize, c: usize) -> usize {
+        (self.get(r - 1, c) as usize) << 6
+            | (self.get(r, c) as usize) << 3
+            | self.get(r + 1, c) as usize
+    }
+
+    fn enhance(&mut self, rule: &Rule, steps: usize) {
+        // every step the known part can grow by the reach of the kernel, and
+        // the pixels just inside the new edge read as far again beyond it
+        let reach = rule.reach();
+        self.reserve((steps + 2) * reach);
+        for _ in 0..steps {
+            self.fill_border(2 * reach);
+
+            let mut out = std::mem::take(&mut self.spare);
+            let (top, bottom) = (self.top - reach, self.top + self.height + reach);
+            let (left, right) = (self.left - reach, self.left + self.width + reach);
+            for r in top..bottom {
+                let row = &mut out[r * self.stride..(r + 1) * self.stride];
+                if rule.moore_3x3 {
+                    let mut idx = self.column(r, left - 1) << 1 | self.column(r, left);
+                    for c in left..right {
+                        idx = (idx << 1) & 0o666 | self.column(r, c + 1);
+                        set_bit(row, c, rule.table[idx]);
+                    }
+                } else {
+                    for c in left..right {
+                        let idx = rule.kernel.iter().fold(0, |idx, d| {
+                            let p = (r as i32 + d.0, c as i32 + d.1);
+                            idx << 1 | self.get(p.0 as usize, p.1 as usize) as usize
+                        });
+                        set_bit(row, c, rule.table[idx]);
+                    }
+                }
+            }
+            self.spare = std::mem::replace(&mut self.bits, out);
+
+            self.top = top;
+            self.left = left;
+            self.height = bottom - top;
+            self.width = right - left;
+            self.outside = rule.table[if self.outside {
+                rule.table.len() - 1
+            } else {
+                0
+            }];
         }
     }
 
-    fn count(&self, x: i32, y: i32) -> i32 {
-        (-1..=1)
-            .flat_map(|r| std::iter::repeat(r).zip(-1..=1))
-            .map(|p| (x + p.0, y + p.1))
-            .map(|p| self.contains(p))
-            .map(|b| if b { 1 } else { 0 })
-            .fold(0, |a, i| i | a << 1)
+    // Shrinks the known part to the smallest rectangle holding every pixel
+    // that differs from the background.
+    fn crop(&mut self) {
+        let differs = |r: usize, c: usize| self.pixel(r, c) != self.outside;
+        let rows = (0..self.height)
+            .filter(|r| (0..self.width).any(|c| differs(*r, c)))
+            .collect::<Vec<_>>();
+        let cols = (0..self.width)
+            .filter(|c| rows.iter().any(|r| differs(*r, *c)))
+            .collect::<Vec<_>>();
+        match (rows.first(), rows.last(), cols.first(), cols.last()) {
+            (Some(&top), Some(&bottom), Some(&left), Some(&right)) => {
+                self.top += top;
+                self.left += left;
+                self.height = bottom - top + 1;
+                self.width = right - left + 1;
+            }
+            _ => {
+                self.height = 0;
+                self.width = 0;
+            }
+        }
     }
 
+    // up to 64 pixels of row r of the known part starting at column c, the
+    // first in the lowest bit
+    fn word(&self, r: usize, c: usize) -> u64 {
+        let (r, c) = (self.top + r, self.left + c);
+        let row = &self.bits[r * self.stride..(r + 1) * self.stride];
+        let (i, shift) = (c / 64, c % 64);
+        let mut ret = row[i] >> shift;
+        if shift > 0 && i + 1 < row.len() {
+            ret |= row[i + 1] << (64 - shift);
+        }
+        let n = self.left + self.width - c;
+        if n < 64 {
+            ret &= (1 << n) - 1;
+        }
+        ret
+    }
+
+    // Calls f with the known part 64 pixels at a time, row by row, the same
+    // way wherever it is within bits.
+    fn for_each_word<F: FnMut(u64)>(&self, mut f: F) {
+        for r in 0..self.height {
+            for c in (0..self.width).step_by(64) {
+                f(self.word(r, c));
+            }
+        }
+    }
+
+    // A hash of the known part and the background.
+    fn hash(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        (self.height, self.width, self.outside).hash(&mut h);
+        self.for_each_word(|w| w.hash(&mut h));
+        h.finish()
+    }
+
+    // whether the known parts and backgrounds are the same
+    fn same(&self, other: &Image) -> bool {
+        if (self.height, self.width, self.outside) != (other.height, other.width, other.outside) {
+            return false;
+        }
+        let mut words = vec![];
+        self.for_each_word(|w| words.push(w));
+        let mut i = 0;
+        let mut same = true;
+        other.for_each_word(|w| {
+            same &= words[i] == w;
+            i += 1;
+        });
+        same
+    }
+
+    // number of lit pixels in the known part
+    fn lit(&self) -> usize {
+        (0..self.height)
+            .map(|r| (0..self.width).filter(|c| self.pixel(r, *c)).count())
+            .sum()
+    }
+
+    // The known part as rows of '#' and '.', each ending in a newline, which
+    // parse reads back.
+    fn to_text(&self) -> String {
+        let mut ret = String::with_capacity((self.width + 1) * self.height);
+        for r in 0..self.height {
+            for c in 0..self.width {
+                ret.push(if self.pixel(r, c) { '#' } else { '.' });
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+
+    #[allow(dead_code)]
     fn print(&self) {
         println!(
-            "width {} height {} surround {} outside {}",
-            self.width, self.height, self.surround, self.outside
+            "width {} height {} outside {}",
+            self.width, self.height, self.outside
         );
-        for x in -self.surround..self.width as i32 + self.surround {
-            for y in -self.surround..self.height as i32 + self.surround {
-                print!("{}", if self.contains((x, y)) { '#' } else { '.' });
-            }
-            println!();
+        print!("{}", self.to_text());
+    }
+
+    // Writes the known part as a plain PBM, with lit pixels black.
+    fn write_pbm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "P1\n{} {}", self.width, self.height)?;
+        self.write_rows(out, "1", "0")
+    }
+
+    // Writes the known part as a plain PGM, with lit pixels white.
+    fn write_pgm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "P2\n{} {}\n255", self.width, self.height)?;
+        self.write_rows(out, "255", "0")
+    }
+
+    fn write_rows<W: Write>(&self, out: &mut W, lit: &str, dark: &str) -> io::Result<()> {
+        for r in 0..self.height {
+            let row = (0..self.width)
+                .map(|c| if self.pixel(r, c) { lit } else { dark })
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", row.join(" "))?;
         }
+        Ok(())
+    }
+}
+
+fn set_bit(words: &mut [u64], i: usize, v: bool) {
+    if v {
+        words[i / 64] |= 1 << (i % 64);
+    } else {
+        words[i / 64] &= !(1 << (i % 64));
     }
 }
 
@@ -61,76 +367,185 @@ fn to_bit(c: char) -> bool {
     }
 }
 
-fn apply(image: &Image, filter: &Vec<bool>) -> Image {
-    let mut ret = Image::default();
-    ret.width = image.width;
-    ret.height = image.height;
-    ret.surround = image.surround + 2;
-    for x in -image.surround..image.width as i32 + image.surround {
-        for y in -image.surround..image.height as i32 + image.surround {
-            let val = image.count(x, y);
-            if filter[val as usize] {
-                ret.known.insert((x, y));
-            }
+// A cellular automaton rule. Each pixel's next value is looked up in table
+// by an index built from the pixels at the kernel's (row, column) offsets,
+// the first offset giving the most significant bit.
+struct Rule {
+    kernel: Vec<(i32, i32)>,
+    table: Vec<bool>,
+    // whether kernel is moore(1), which enhance has a fast path for
+    moore_3x3: bool,
+}
+
+impl Rule {
+    fn new(kernel: Vec<(i32, i32)>, table: Vec<bool>) -> Rule {
+        assert_eq!(
+            table.len(),
+            1 << kernel.len(),
+            "table must have 2^k entries"
+        );
+        let moore_3x3 = kernel == moore(1);
+        Rule {
+            kernel,
+            table,
+            moore_3x3,
         }
     }
-    if image.outside {
-        ret.outside = filter[511]
-    } else {
-        ret.outside = filter[0]
+
+    // the puzzle's rule: a 3x3 kernel read row by row
+    fn trench_map(filter: &[bool]) -> Rule {
+        Rule::new(moore(1), filter.to_vec())
+    }
+
+    // Parses a Life-like rule such as "B3/S23", where a dark pixel is born
+    // with any of the B counts of lit neighbours and a lit pixel survives
+    // with any of the S counts. Neighbours are the rest of the kernel, which
+    // must include (0, 0). Each count is a single digit unless they are
+    // separated by commas, as in "B3,10/S2,3,12" for bigger kernels.
+    #[allow(dead_code)]
+    fn life_like(spec: &str, kernel: Vec<(i32, i32)>) -> Result<Rule, String> {
+        let centre = kernel
+            .iter()
+            .position(|d| *d == (0, 0))
+            .ok_or("kernel must include (0, 0)")?;
+        let neighbours = kernel.len() as u32 - 1;
+
+        let counts = |prefix: char| -> Result<Vec<u32>, String> {
+            let part = spec
+                .split('/')
+                .find(|p| p.to_ascii_uppercase().starts_with(prefix))
+                .ok_or_else(|| format!("no {} in rule {:?}", prefix, spec))?;
+            let counts = &part[1..];
+            let counts = match counts.contains(',') {
+                true => counts.split(',').collect::<Vec<_>>(),
+                false => counts.matches(|_| true).collect(),
+            };
+            counts
+                .iter()
+                .map(|n| match n.trim().parse::<u32>() {
+                    Ok(n) if n <= neighbours => Ok(n),
+                    Ok(n) => Err(format!(
+                        "{} neighbours in rule {:?}, but the kernel only has {}",
+                        n, spec, neighbours
+                    )),
+                    Err(_) => Err(format!("bad count {:?} in rule {:?}", n, spec)),
+                })
+                .collect()
+        };
+        let (born, survive) = (counts('B')?, counts('S')?);
+
+        let centre_bit = 1 << (kernel.len() - 1 - centre);
+        let table = (0..1usize << kernel.len())
+            .map(|idx| {
+                let neighbours = (idx & !centre_bit).count_ones();
+                match idx & centre_bit != 0 {
+                    true => survive.contains(&neighbours),
+                    false => born.contains(&neighbours),
+                }
+            })
+            .collect();
+        Ok(Rule::new(kernel, table))
+    }
+
+    // furthest the kernel reaches in any direction
+    fn reach(&self) -> usize {
+        self.kernel
+            .iter()
+            .map(|d| d.0.unsigned_abs().max(d.1.unsigned_abs()) as usize)
+            .max()
+            .unwrap_or(0)
     }
-    return ret;
 }
 
-fn part1(lines: &[&str]) -> i32 {
-    let filter = lines[0].chars().map(to_bit).collect::<Vec<_>>();
+// every offset within radius in both directions, row by row
+fn moore(radius: i32) -> Vec<(i32, i32)> {
+    (-radius..=radius)
+        .flat_map(|r| (-radius..=radius).map(move |c| (r, c)))
+        .collect()
+}
 
-    let image_vec = lines[2..lines.len()]
-        .iter()
-        .map(|l| l.chars().map(to_bit).collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-
-    let mut image = Image::default();
-    image.height = image_vec.len() as i32;
-    image.width = image_vec[0].len() as i32;
-    image.surround = 2;
-    for r in 0..image.height {
-        for c in 0..image.width {
-            if image_vec[r as usize][c as usize] {
-                image.known.insert((r, c));
-            }
+// every offset within radius steps up, down, left or right, row by row
+#[allow(dead_code)]
+fn von_neumann(radius: i32) -> Vec<(i32, i32)> {
+    moore(radius)
+        .into_iter()
+        .filter(|d| d.0.abs() + d.1.abs() <= radius)
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Lit {
+    Count(usize),
+    Infinite,
+}
+
+impl fmt::Display for Lit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lit::Count(n) => write!(f, "{}", n),
+            Lit::Infinite => write!(f, "infinity"),
         }
     }
-
-    image = apply(&image, &filter);
-    image = apply(&image, &filter);
-    return image.known.len() as i32;
 }
 
-fn part2(lines: &[&str]) -> i32 {
-    let filter = lines[0].chars().map(to_bit).collect::<Vec<_>>();
+// Returns how many pixels are lit after enhancing image steps times.
+// Images are cropped to the pixels that differ from the background after
+// every step so that a repeated image, wherever it has drifted to, is
+// spotted and the answer is read off from the cycle instead of iterating.
+// Only a hash of each image is kept, so when one matches, the earlier image
+// is enhanced again from the start to make sure it really is the same.
+fn lit_after(image: &mut Image, rule: &Rule, steps: usize) -> Lit {
+    let first = image.clone();
+    let mut seen = HashMap::<u64, Vec<usize>>::new();
+    let mut lits = vec![];
+
+    let next = |image: &mut Image, step: usize| {
+        if step > 0 {
+            image.enhance(rule, 1);
+        }
+        image.crop();
+    };
+    for step in 0.. {
+        next(image, step);
+        lits.push(match image.outside {
+            true => Lit::Infinite,
+            false => Lit::Count(image.lit()),
+        });
+        if step == steps {
+            break;
+        }
 
-    let image_vec = lines[2..lines.len()]
-        .iter()
-        .map(|l| l.chars().map(to_bit).collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-
-    let mut image = Image::default();
-    image.height = image_vec.len() as i32;
-    image.width = image_vec[0].len() as i32;
-    image.surround = 2;
-    for r in 0..image.height {
-        for c in 0..image.width {
-            if image_vec[r as usize][c as usize] {
-                image.known.insert((r, c));
+        let earlier = seen.entry(image.hash()).or_default();
+        for &start in earlier.iter() {
+            let mut then = first.clone();
+            (0..=start).for_each(|s| next(&mut then, s));
+            if then.same(image) {
+                let period = step - start;
+                return lits[start + (steps - start) % period];
             }
         }
+        earlier.push(step);
     }
+    lits[steps]
+}
+
+fn enhance(lines: &[&str], steps: usize) -> i32 {
+    let filter = lines[0].chars().map(to_bit).collect::<Vec<_>>();
 
-    for _ in 0..50 {
-        image = apply(&image, &filter);
+    let mut image = Image::parse(&lines[2..]);
+    image.enhance(&Rule::trench_map(&filter), steps);
+    match image.outside {
+        false => image.lit() as i32,
+        true => panic!("infinitely many pixels are lit"),
     }
-    return image.known.len() as i32;
+}
+
+fn part1(lines: &[&str]) -> i32 {
+    enhance(lines, 2)
+}
+
+fn part2(lines: &[&str]) -> i32 {
+    enhance(lines, 50)
 }
 
 #[cfg(test)]
@@ -163,4 +578,168 @@ mod tests {
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn orientation() {
+        let lines = ["#..", ".##"];
+        let image = Image::parse(&lines);
+        assert_eq!(image.to_text(), "#..\n.##\n");
+        assert!(image.pixel(0, 0) && !image.pixel(0, 2) && image.pixel(1, 2));
+
+        // each pixel becomes whatever was to its left
+        let rule = Rule::trench_map(&(0..512).map(|i| i & 0o040 != 0).collect::<Vec<_>>());
+        let mut image = Image::parse(&lines);
+        image.enhance(&rule, 1);
+        let text = image.to_text();
+        assert_eq!(text, ".....\n..#..\n...##\n.....\n");
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(Image::parse(&lines).to_text(), text);
+
+        let mut pbm = vec![];
+        image.write_pbm(&mut pbm).unwrap();
+        assert_eq!(
+            String::from_utf8(pbm).unwrap(),
+            "P1\n5 4\n0 0 0 0 0\n0 0 1 0 0\n0 0 0 1 1\n0 0 0 0 0\n"
+        );
+        let mut pgm = vec![];
+        image.write_pgm(&mut pgm).unwrap();
+        assert!(String::from_utf8(pgm)
+            .unwrap()
+            .starts_with("P2\n5 4\n255\n0 0 0 0 0\n0 0 255 0 0\n"));
+    }
+
+    #[test]
+    fn cycles() {
+        let lines = ["#..#.", "#....", "##..#", "..#..", "..###"];
+        let huge = 1_000_000_000_000_000;
+
+        // every pixel stays the same
+        let rule = Rule::trench_map(&(0..512).map(|i| i & 0o020 != 0).collect::<Vec<_>>());
+        let mut image = Image::parse(&lines);
+        assert_eq!(lit_after(&mut image, &rule, huge), Lit::Count(10));
+
+        // every pixel moves right, so the image never repeats in place
+        let rule = Rule::trench_map(&(0..512).map(|i| i & 0o040 != 0).collect::<Vec<_>>());
+        let mut image = Image::parse(&lines);
+        assert_eq!(lit_after(&mut image, &rule, huge), Lit::Count(10));
+
+        // every pixel flips, including the background
+        let rule = Rule::trench_map(&(0..512).map(|i| i & 0o020 == 0).collect::<Vec<_>>());
+        let mut image = Image::parse(&lines);
+        assert_eq!(lit_after(&mut image, &rule, huge), Lit::Count(10));
+        let mut image = Image::parse(&lines);
+        assert_eq!(lit_after(&mut image, &rule, huge + 1), Lit::Infinite);
+        assert_eq!(Lit::Infinite.to_string(), "infinity");
+
+        // nothing survives
+        let rule = Rule::trench_map(&vec![false; 512]);
+        let mut image = Image::parse(&lines);
+        assert_eq!(lit_after(&mut image, &rule, huge), Lit::Count(0));
+
+        // the same picture anywhere in the bitset hashes the same
+        let wide = ["#".repeat(70) + "." + &"#".repeat(70)];
+        let wide = wide.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+        let mut a = Image::parse(&wide);
+        let b = a.clone();
+        a.reserve(100);
+        assert!(a.left != b.left);
+        assert_eq!(a.hash(), b.hash());
+        assert!(a.same(&b));
+        a.set(a.top, a.left + 70, true);
+        assert!(a.hash() != b.hash());
+        assert!(!a.same(&b));
+    }
+
+    #[test]
+    fn kernels() {
+        let dot = ["#"];
+
+        // lit if anything in the kernel is lit
+        let grow = |kernel: Vec<(i32, i32)>| {
+            let table = (0..1 << kernel.len()).map(|i| i != 0).collect();
+            Rule::new(kernel, table)
+        };
+        let mut image = Image::parse(&dot);
+        image.enhance(&grow(von_neumann(1)), 2);
+        assert_eq!(image.lit(), 13);
+        assert_eq!(image.to_text(), "..#..\n.###.\n#####\n.###.\n..#..\n");
+        let mut image = Image::parse(&dot);
+        image.enhance(&grow(moore(2)), 1);
+        assert_eq!(image.lit(), 25);
+
+        // a lit background stays lit
+        let mut image = Image::parse(&dot);
+        let rule = Rule::life_like("B0/S8", moore(1)).unwrap();
+        image.enhance(&rule, 1);
+        assert!(image.outside);
+        image.enhance(&rule, 1);
+        assert!(image.outside);
+        assert_eq!(lit_after(&mut image, &rule, 1), Lit::Infinite);
+
+        // the same rule with the kernel read backwards doesn't take the 3x3
+        // fast path, but must agree with it
+        let lines = ["#..#.", "#....", "##..#", "..#..", "..###"];
+        let filter = (0..512).map(|i| i % 7 < 3).collect::<Vec<_>>();
+        let reversed = Rule::new(
+            moore(1).into_iter().rev().collect(),
+            (0..512)
+                .map(|i: usize| filter[i.reverse_bits() >> (usize::BITS - 9)])
+                .collect(),
+        );
+        let mut fast = Image::parse(&lines);
+        fast.enhance(&Rule::trench_map(&filter), 3);
+        let mut slow = Image::parse(&lines);
+        slow.enhance(&reversed, 3);
+        assert_eq!(fast.to_text(), slow.to_text());
+
+        let life = Rule::life_like("B3/S23", moore(1)).unwrap();
+        let mut blinker = Image::parse(&["###"]);
+        blinker.enhance(&life, 1);
+        blinker.crop();
+        assert_eq!(blinker.to_text(), "#\n#\n#\n");
+
+        let glider = [".#.", "..#", "###"];
+        let mut image = Image::parse(&glider);
+        assert_eq!(lit_after(&mut image, &life, 1_000_000_000), Lit::Count(5));
+        let mut image = Image::parse(&glider);
+        image.enhance(&life, 4);
+        image.crop();
+        assert_eq!(image.to_text(), ".#.\n..#\n###\n");
+
+        // counts of 10 or more need commas
+        let big = Rule::life_like("B10,24/S", moore(2)).unwrap();
+        assert!(big.table[0b1111111111]);
+        assert!(!big.table[0b111111111]);
+        // the hole sees 24, and the pixels just off the middle of each side
+        // see two full rows of 5
+        let mut image = Image::parse(&["#####", "#####", "##.##", "#####", "#####"]);
+        image.enhance(&big, 1);
+        image.crop();
+        assert_eq!(
+            image.to_text(),
+            "...#...\n.......\n.......\n#..#..#\n.......\n.......\n...#...\n"
+        );
+        assert_eq!(
+            Rule::life_like("B3,10/S2,3", moore(1)).err().unwrap(),
+            "10 neighbours in rule \"B3,10/S2,3\", but the kernel only has 8"
+        );
+        assert!(Rule::life_like("B3", moore(1)).is_err());
+        assert!(Rule::life_like("B3x/S23", moore(1)).is_err());
+        assert!(Rule::life_like("B3/S23", vec![(0, 1)]).is_err());
+    }
+
+    #[test]
+    fn regrow() {
+        let lines = ["#.#", "...", ".##"];
+        let rule = Rule::trench_map(&(0..512).map(|i| i % 3 == 0).collect::<Vec<_>>());
+        let mut once = Image::parse(&lines);
+        once.enhance(&rule, 9);
+        let mut split = Image::parse(&lines);
+        for steps in [1, 3, 5] {
+            split.enhance(&rule, steps);
+        }
+        assert_eq!((split.height, split.width), (once.height, once.width));
+        assert_eq!(split.outside, once.outside);
+        assert_eq!(split.lit(), once.lit());
+    }
 }
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::fs;
 use std::ops::RangeInclusive;
+use std::thread;
 
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
@@ -12,9 +14,8 @@ fn main() {
 
 struct Step {
     on: bool,
-    x_range: RangeInclusive<i64>,
-    y_range: RangeInclusive<i64>,
-    z_range: RangeInclusive<i64>,
+    // one inclusive range per axis, in the order they appear in the line
+    ranges: Vec<RangeInclusive<i64>>,
 }
 
 impl Step {
@@ -22,21 +23,19 @@ impl Step {
         let parts = l.split_whitespace().collect::<Vec<_>>();
         let on = parts[0] == "on";
         let ranges = parts[1]
-            .split(",")
-            .map(|p| p.trim_start_matches(&['x', 'y', 'z', '='][..]))
+            .split(',')
+            .map(|p| p.split_once('=').map_or(p, |(_, r)| r))
             .map(|s| {
-                s.split("..")
-                    .map(|s| s.parse::<i64>().unwrap())
-                    .collect::<Vec<_>>()
+                let (min, max) = s.split_once("..").unwrap();
+                min.parse::<i64>().unwrap()..=max.parse::<i64>().unwrap()
             })
             .collect::<Vec<_>>();
 
-        return Step {
-            on,
-            x_range: ranges[0][0]..=ranges[0][1],
-            y_range: ranges[1][0]..=ranges[1][1],
-            z_range: ranges[2][0]..=ranges[2][1],
-        };
+        Step { on, ranges }
+    }
+
+    fn dims(&self) -> usize {
+        self.ranges.len()
     }
 }
 
@@ -47,47 +46,48 @@ impl Step {
 
 #[derive(Default)]
 struct Reactor2 {
-    // distinct values in [x, y, z]
-    vals: [Vec<i64>; 3],
+    // distinct values along each axis
+    vals: Vec<Vec<i64>>,
     // maps from values to indices
-    to_vals: [HashMap<i64, usize>; 3],
-
-    // "on" means the cells >= vals[x][y][z] and <=
-    on: Vec<Vec<Vec<bool>>>,
+    to_vals: Vec<HashMap<i64, usize>>,
+
+    // number of compressed cells along each axis
+    sizes: Vec<usize>,
+    // words per slab, where a slab is all of the cells sharing one index on
+    // the first axis
+    slab_words: usize,
+    // bitset of "on" for each compressed cell. Within a slab the last axis
+    // varies fastest; each slab starts on a fresh word so that slabs can be
+    // handed to different threads.
+    on: Vec<u64>,
 }
 
 impl Reactor2 {
     fn build(&mut self, steps: &[Step]) {
+        let dims = steps[0].dims();
+        self.vals = vec![vec![]; dims];
         for s in steps {
-            self.vals[0].push(*s.x_range.start());
-            self.vals[0].push(*s.x_range.end() + 1);
-
-            self.vals[1].push(*s.y_range.start());
-            self.vals[1].push(*s.y_range.end() + 1);
-
-            self.vals[2].push(*s.z_range.start());
-            self.vals[2].push(*s.z_range.end() + 1);
+            assert_eq!(s.dims(), dims, "all steps must have the same dimension");
+            for (axis, r) in s.ranges.iter().enumerate() {
+                self.vals[axis].push(*r.start());
+                self.vals[axis].push(*r.end() + 1);
+            }
         }
-        let mut sizes = [0; 3];
-        for i in 0..3 {
-            self.vals[i].sort();
+        self.to_vals = vec![HashMap::new(); dims];
+        self.sizes = vec![0; dims];
+        for i in 0..dims {
+            self.vals[i].sort_unstable();
             self.vals[i].dedup();
-            sizes[i] = self.vals[i].len() - 1;
+            self.sizes[i] = self.vals[i].len() - 1;
 
-            self.to_vals[i] = HashMap::new();
-
-            for j in 0..self.vals[i].len() {
-                self.to_vals[i].insert(self.vals[i][j], j);
+            for (j, v) in self.vals[i].iter().enumerate() {
+                self.to_vals[i].insert(*v, j);
             }
         }
 
-        self.on.resize(sizes[0], vec![]);
-        for x in 0..sizes[0] {
-            self.on[x].resize(sizes[1], vec![]);
-            for y in 0..sizes[1] {
-                self.on[x][y].resize(sizes[2], false);
-            }
-        }
+        let slab_bits = self.sizes[1..].iter().product::<usize>();
+        self.slab_words = slab_bits.div_ceil(64);
+        self.on = vec![0; self.sizes[0] * self.slab_words];
     }
 
     fn map_vals(&self, axis: usize, min: i64, max: i64) -> (usize, usize) {
@@ -97,77 +97,223 @@ impl Reactor2 {
         )
     }
 
-    fn apply(&mut self, s: &Step) {
-        let x_mapped = self.map_vals(0, *s.x_range.start(), *s.x_range.end());
-        let y_mapped = self.map_vals(1, *s.y_range.start(), *s.y_range.end());
-        let z_mapped = self.map_vals(2, *s.z_range.start(), *s.z_range.end());
+    // bit offset within a slab of the first cell in the row (along the last
+    // axis) containing cell, where cell gives coordinates for every axis
+    // except the first and the last
+    fn row_index(&self, cell: &[usize]) -> usize {
+        cell.iter()
+            .zip(&self.sizes[1..])
+            .fold(0, |acc, (c, size)| acc * size + c)
+            * self.sizes[self.sizes.len() - 1]
+    }
+
+    // number of slabs given to each thread
+    fn slabs_per_thread(&self) -> usize {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        self.sizes[0].div_ceil(threads).max(1)
+    }
+
+    // Applies every step in order. Each thread owns a run of slabs and applies
+    // all of the steps to it, so no synchronisation is needed.
+    fn apply(&mut self, steps: &[Step]) {
+        // every range along some axis after the first is empty, so there
+        // are no cells to turn on
+        if self.slab_words == 0 {
+            return;
+        }
+        let mapped = steps
+            .iter()
+            .map(|s| {
+                let bounds = s
+                    .ranges
+                    .iter()
+                    .enumerate()
+                    .map(|(axis, r)| self.map_vals(axis, *r.start(), *r.end()))
+                    .collect::<Vec<_>>();
+                (s.on, bounds)
+            })
+            .collect::<Vec<_>>();
 
-        for x in x_mapped.0..x_mapped.1 {
-            for y in y_mapped.0..y_mapped.1 {
-                for z in z_mapped.0..z_mapped.1 {
-                    self.on[x][y][z] = s.on;
-                }
+        let mut on = std::mem::take(&mut self.on);
+        let per_thread = self.slabs_per_thread();
+        let reactor = &*self;
+        thread::scope(|scope| {
+            for (i, chunk) in on.chunks_mut(per_thread * reactor.slab_words).enumerate() {
+                let mapped = &mapped;
+                scope.spawn(move || {
+                    let first = i * per_thread;
+                    for (x, slab) in chunk.chunks_mut(reactor.slab_words).enumerate() {
+                        for (step_on, bounds) in mapped {
+                            if (bounds[0].0..bounds[0].1).contains(&(first + x)) {
+                                reactor.apply_slab(slab, &bounds[1..], *step_on);
+                            }
+                        }
+                    }
+                });
             }
+        });
+        self.on = on;
+    }
+
+    fn apply_slab(&self, slab: &mut [u64], bounds: &[(usize, usize)], on: bool) {
+        match bounds.split_last() {
+            None => set_bits(slab, 0, 1, on),
+            Some(((start, end), leading)) => for_each_cell(leading, |cell| {
+                let row = self.row_index(cell);
+                set_bits(slab, row + start, row + end, on);
+            }),
         }
     }
 
     fn count(&self, restrict: bool) -> i64 {
-        let mut ret = 0;
+        if self.slab_words == 0 {
+            return 0;
+        }
+        let per_thread = self.slabs_per_thread();
+        thread::scope(|scope| {
+            let handles = self
+                .on
+                .chunks(per_thread * self.slab_words)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    scope.spawn(move || {
+                        chunk
+                            .chunks(self.slab_words)
+                            .enumerate()
+                            .map(|(x, slab)| self.count_slab(i * per_thread + x, slab, restrict))
+                            .sum::<i64>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        })
+    }
 
-        for x in 0..self.on.len() {
-            for y in 0..self.on[x].len() {
-                for z in 0..self.on[x][y].len() {
-                    if self.on[x][y][z] {
-                        ret += self.volume(x, y, z, restrict);
-                    }
-                }
-            }
+    fn count_slab(&self, x: usize, slab: &[u64], restrict: bool) -> i64 {
+        let width = self.length(0, x, restrict);
+        let last = self.sizes.len() - 1;
+        if width == 0 {
+            return 0;
+        }
+        if last == 0 {
+            return if slab[0] & 1 != 0 { width } else { 0 };
         }
 
-        return ret;
+        let mut ret = 0;
+        let row_len = self.sizes[last];
+        let leading = self.sizes[1..last]
+            .iter()
+            .map(|&size| (0, size))
+            .collect::<Vec<_>>();
+        for_each_cell(&leading, |cell| {
+            let row = self.row_index(cell);
+            let mut len = 0;
+            for_each_set_bit(slab, row, row + row_len, |i| {
+                len += self.length(last, i - row, restrict);
+            });
+            if len > 0 {
+                ret += len * self.volume(cell, restrict);
+            }
+        });
+
+        ret * width
+    }
+
+    // volume of the compressed cell, which gives coordinates for the axes
+    // between the first and the last
+    fn volume(&self, cell: &[usize], restrict: bool) -> i64 {
+        cell.iter()
+            .enumerate()
+            .map(|(axis, &i)| self.length(axis + 1, i, restrict))
+            .product()
     }
 
-    fn volume(&self, x: usize, y: usize, z: usize, restrict: bool) -> i64 {
-        let mut x_range = [self.vals[0][x + 1], self.vals[0][x]];
-        let mut y_range = [self.vals[1][y + 1], self.vals[1][y]];
-        let mut z_range = [self.vals[2][z + 1], self.vals[2][z]];
+    fn length(&self, axis: usize, i: usize, restrict: bool) -> i64 {
+        let mut range = [self.vals[axis][i + 1], self.vals[axis][i]];
         if restrict {
-            for i in 0..2 {
-                x_range[i] = x_range[i].clamp(-50, 51);
-                y_range[i] = y_range[i].clamp(-50, 51);
-                z_range[i] = z_range[i].clamp(-50, 51);
+            for v in range.iter_mut() {
+                *v = (*v).clamp(-50, 51);
             }
         }
-        (x_range[0] - x_range[1]) * (y_range[0] - y_range[1]) * (z_range[0] - z_range[1])
+        range[0] - range[1]
     }
 }
 
-fn part1(lines: &[&str]) -> i32 {
-    let steps = lines.iter().map(|l| Step::parse(l)).collect::<Vec<_>>();
-
-    let mut reactor = Reactor2::default();
-
-    reactor.build(&steps);
+// Sets or clears bits [start, end) of words.
+fn set_bits(words: &mut [u64], start: usize, end: usize, on: bool) {
+    let mut i = start;
+    while i < end {
+        let bit = i % 64;
+        let n = (64 - bit).min(end - i);
+        let mask = if n == 64 { !0 } else { ((1 << n) - 1) << bit };
+        if on {
+            words[i / 64] |= mask;
+        } else {
+            words[i / 64] &= !mask;
+        }
+        i += n;
+    }
+}
 
-    for s in steps {
-        reactor.apply(&s);
+// Calls f with the index of every set bit in [start, end) of words.
+fn for_each_set_bit<F: FnMut(usize)>(words: &[u64], start: usize, end: usize, mut f: F) {
+    let mut i = start;
+    while i < end {
+        let bit = i % 64;
+        let n = (64 - bit).min(end - i);
+        let mut w = words[i / 64] >> bit;
+        if n < 64 {
+            w &= (1 << n) - 1;
+        }
+        while w != 0 {
+            f(i + w.trailing_zeros() as usize);
+            w &= w - 1;
+        }
+        i += n;
     }
+}
 
-    return reactor.count(true) as i32;
+// Calls f with every cell in the half-open box described by bounds, one
+// (start, end) pair per axis.
+fn for_each_cell<F: FnMut(&[usize])>(bounds: &[(usize, usize)], mut f: F) {
+    if bounds.iter().any(|(start, end)| start >= end) {
+        return;
+    }
+    let mut cell = bounds.iter().map(|(start, _)| *start).collect::<Vec<_>>();
+    loop {
+        f(&cell);
+        let mut axis = cell.len();
+        loop {
+            if axis == 0 {
+                return;
+            }
+            axis -= 1;
+            cell[axis] += 1;
+            if cell[axis] < bounds[axis].1 {
+                break;
+            }
+            cell[axis] = bounds[axis].0;
+        }
+    }
 }
 
-fn part2(lines: &[&str]) -> i64 {
+fn reboot(lines: &[&str], restrict: bool) -> i64 {
     let steps = lines.iter().map(|l| Step::parse(l)).collect::<Vec<_>>();
 
     let mut reactor = Reactor2::default();
 
     reactor.build(&steps);
+    reactor.apply(&steps);
 
-    for s in steps {
-        reactor.apply(&s);
-    }
+    reactor.count(restrict)
+}
 
-    return reactor.count(false);
+fn part1(lines: &[&str]) -> i32 {
+    reboot(lines, true) as i32
+}
+
+fn part2(lines: &[&str]) -> i64 {
+    reboot(lines, false)
 }
 
 #[cfg(test)]
@@ -293,4 +439,41 @@ mod tests {
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
         }
     }
+
+    #[test]
+    fn bits() {
+        let mut words = vec![0; 3];
+        set_bits(&mut words, 60, 130, true);
+        set_bits(&mut words, 64, 128, false);
+        let mut set = vec![];
+        for_each_set_bit(&words, 0, 192, |i| set.push(i));
+        assert_eq!(set, vec![60, 61, 62, 63, 128, 129]);
+    }
+
+    #[test]
+    fn other_dimensions() {
+        assert_eq!(
+            reboot(&["on x=0..9,y=0..9", "off x=5..14,y=5..14"], false),
+            75
+        );
+        assert_eq!(reboot(&["on x=-60..60"], true), 101);
+        assert_eq!(
+            reboot(
+                &[
+                    "on x=0..1,y=0..1,z=0..1,w=0..1",
+                    "on x=1..2,y=1..2,z=1..2,w=1..2",
+                    "off x=0..0,y=0..0,z=0..0,w=0..0",
+                ],
+                false
+            ),
+            30
+        );
+        // empty ranges leave nothing to turn on
+        assert_eq!(reboot(&["on x=0..3,y=5..4"], false), 0);
+        assert_eq!(reboot(&["on x=0..3,y=5..4,z=1..2"], false), 0);
+        assert_eq!(
+            reboot(&["on x=0..3,y=0..3", "off x=1..1,y=2..1"], false),
+            16
+        );
+    }
 }
@@ -1,127 +1,347 @@
+use std::env;
 use std::fs;
 
+// Pass --frames to print the grid after every step, or --report to print
+// per-step statistics as CSV.
 fn main() {
     let filename = "input.txt";
-    let contents = fs::read_to_string(filename).expect(&format!("could not read {}", filename));
+    let contents =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("could not read {}", filename));
     let lines: Vec<&str> = contents.lines().collect();
     println!("part1 is {}", part1(&lines));
     println!("part2 is {}", part2(&lines));
+    println!("total moves is {}", total_moves(&lines));
+
+    let frames = env::args().any(|a| a == "--frames");
+    let report = env::args().any(|a| a == "--report");
+    if frames || report {
+        let mut grid = Grid::parse(&lines);
+        let stats = simulate(&mut grid, frames);
+        if report {
+            print!("{}", to_csv(&grid.herds, &stats));
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-enum Thing {
-    Down,
-    Left,
-    Empty,
+// A herd of sea cucumbers, drawn as glyph, that all try to move one cell
+// by dir, given as (row, column) steps of -1, 0 or 1, each step.
+#[derive(Debug, Clone)]
+struct Herd {
+    name: String,
+    glyph: char,
+    dir: (i32, i32),
+}
+
+impl Herd {
+    fn new(name: &str, glyph: char, dir: (i32, i32)) -> Herd {
+        assert!(dir != (0, 0), "herd {} has to move", name);
+        assert!(
+            (-1..=1).contains(&dir.0) && (-1..=1).contains(&dir.1),
+            "herd {} can only move one cell",
+            name
+        );
+        Herd {
+            name: name.to_string(),
+            glyph,
+            dir,
+        }
+    }
 }
 
+// the puzzle's herds: east then south
+fn default_herds() -> Vec<Herd> {
+    vec![
+        Herd::new("east", '>', (0, 1)),
+        Herd::new("south", 'v', (1, 0)),
+    ]
+}
+
+// The sea floor as one bitset per herd per row, where bit c of a row is set
+// if that herd has a sea cucumber in column c. Herds move in the order
+// they're listed.
 struct Grid {
-    vals: Vec<Vec<Thing>>,
+    herds: Vec<Herd>,
+    cells: Vec<Vec<Vec<u64>>>,
     height: usize,
     width: usize,
+
+    // the sea cucumbers able to move in the herd being moved, kept here to
+    // save allocating each step
+    movers: Vec<Vec<u64>>,
 }
 
 impl Grid {
     fn parse(lines: &[&str]) -> Grid {
-        let vals = lines
-            .iter()
-            .map(|l| {
-                l.chars()
-                    .map(|c| match c {
-                        'v' => Thing::Down,
-                        '>' => Thing::Left,
-                        '.' => Thing::Empty,
-                        _ => panic!("unknown char"),
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
-        let height = vals.len();
-        let width = vals[0].len();
-        Grid {
-            vals,
+        Grid::parse_with(lines, default_herds())
+    }
+
+    // Parses lines where each cell is '.' or the glyph of one of herds.
+    fn parse_with(lines: &[&str], herds: Vec<Herd>) -> Grid {
+        let height = lines.len();
+        let width = lines[0].len();
+        let words = width.div_ceil(64);
+        let mut grid = Grid {
+            cells: vec![vec![vec![0; words]; height]; herds.len()],
+            herds,
             height,
             width,
+            movers: vec![vec![0; words]; height],
+        };
+        for (r, l) in lines.iter().enumerate() {
+            for (c, ch) in l.chars().enumerate() {
+                if ch == '.' {
+                    continue;
+                }
+                let h = grid
+                    .herds
+                    .iter()
+                    .position(|herd| herd.glyph == ch)
+                    .expect("unknown char");
+                grid.cells[h][r][c / 64] |= 1 << (c % 64);
+            }
         }
+        grid
     }
-    fn next(&self, r: usize, c: usize, dir: Thing) -> (usize, usize) {
-        match dir {
-            Thing::Down => ((r + 1) % self.height, c),
-            Thing::Left => (r, (c + 1) % self.width),
-            Thing::Empty => panic!(),
-        }
+
+    // index of the herd with a sea cucumber at (r, c), if any
+    fn get(&self, r: usize, c: usize) -> Option<usize> {
+        (0..self.herds.len()).find(|h| self.cells[*h][r][c / 64] >> (c % 64) & 1 != 0)
+    }
+
+    fn next(&self, r: usize, c: usize, dir: (i32, i32)) -> (usize, usize) {
+        (
+            (r as i32 + dir.0).rem_euclid(self.height as i32) as usize,
+            (c as i32 + dir.1).rem_euclid(self.width as i32) as usize,
+        )
     }
 
-    fn move_herd(&mut self, kind: Thing) -> bool {
-        let mut moved_anything = false;
+    // Moves every member of the herd that can and returns how many did. A
+    // sea cucumber can move if the bit for the cell in front of it is set in
+    // the bitset of empty cells, so the movers in each row are that row of
+    // the herd and'ed with the empty row in front, rotated by the column
+    // step. They're all found before any move.
+    fn move_herd(&mut self, h: usize) -> usize {
+        let (dr, dc) = self.herds[h].dir;
+        let words = self.width.div_ceil(64);
+        let mut empty = vec![0; words];
+        let mut moved_to = vec![0; words];
 
-        let mut can_move = vec![];
-        can_move.resize(self.height, vec![]);
         for r in 0..self.height {
-            can_move[r].resize(self.width, false);
+            let ahead = (r as i32 + dr).rem_euclid(self.height as i32) as usize;
+            for (i, word) in empty.iter_mut().enumerate() {
+                *word = !self
+                    .cells
+                    .iter()
+                    .fold(0, |occupied, herd| occupied | herd[ahead][i]);
+            }
+            mask_width(&mut empty, self.width);
+            let movers = &mut self.movers[r];
+            rotate(&empty, self.width, dc, movers);
+            for (i, word) in movers.iter_mut().enumerate() {
+                *word &= self.cells[h][r][i];
+            }
         }
-        // check which can move
+
+        let mut moved = 0;
         for r in 0..self.height {
-            for c in 0..self.width {
-                if self.vals[r][c] == kind {
-                    let n = self.next(r, c, kind);
-                    can_move[r][c] = self.vals[n.0][n.1] == Thing::Empty;
-                }
+            for (i, word) in self.cells[h][r].iter_mut().enumerate() {
+                *word &= !self.movers[r][i];
+                moved += self.movers[r][i].count_ones() as usize;
             }
         }
-        // now move
         for r in 0..self.height {
-            for c in 0..self.width {
-                if can_move[r][c] {
-                    let n = self.next(r, c, kind);
-                    self.vals[r][c] = Thing::Empty;
-                    self.vals[n.0][n.1] = kind;
-                    moved_anything = true;
-                }
+            let behind = (r as i32 - dr).rem_euclid(self.height as i32) as usize;
+            rotate(&self.movers[behind], self.width, -dc, &mut moved_to);
+            for (i, word) in self.cells[h][r].iter_mut().enumerate() {
+                *word |= moved_to[i];
             }
         }
+        moved
+    }
 
-        return moved_anything;
+    // Moves each herd in turn, returning how many of each moved.
+    fn step(&mut self) -> Vec<usize> {
+        (0..self.herds.len()).map(|h| self.move_herd(h)).collect()
     }
 
-    fn step(&mut self) -> bool {
-        self.move_herd(Thing::Left) | self.move_herd(Thing::Down)
+    // Length of the longest line of sea cucumbers from one herd, nose to
+    // tail along their direction of travel, where the one at the front is
+    // blocked by another herd and so none of them can move. A herd filling
+    // a whole loop around the sea floor is jammed too.
+    fn largest_jam(&self) -> usize {
+        let mut largest = 0;
+        for (h, herd) in self.herds.iter().enumerate() {
+            let mut seen = vec![vec![false; self.width]; self.height];
+            let back = (-herd.dir.0, -herd.dir.1);
+
+            // count back from the front of each blocked line
+            for r in 0..self.height {
+                for c in 0..self.width {
+                    let n = self.next(r, c, herd.dir);
+                    let ahead = self.get(n.0, n.1);
+                    if self.get(r, c) != Some(h) || ahead.is_none() || ahead == Some(h) {
+                        continue;
+                    }
+                    let mut p = (r, c);
+                    let mut len = 0;
+                    while self.get(p.0, p.1) == Some(h) {
+                        seen[p.0][p.1] = true;
+                        len += 1;
+                        p = self.next(p.0, p.1, back);
+                    }
+                    largest = largest.max(len);
+                }
+            }
+
+            // anything left is either in a line that can move or in a loop
+            for r in 0..self.height {
+                for c in 0..self.width {
+                    if self.get(r, c) != Some(h) || seen[r][c] {
+                        continue;
+                    }
+                    let mut p = (r, c);
+                    let mut len = 0;
+                    while self.get(p.0, p.1) == Some(h) && !seen[p.0][p.1] {
+                        seen[p.0][p.1] = true;
+                        len += 1;
+                        p = self.next(p.0, p.1, herd.dir);
+                    }
+                    if p == (r, c) {
+                        largest = largest.max(len);
+                    }
+                }
+            }
+        }
+        largest
     }
 
     fn print(&self) {
         for r in 0..self.height {
             for c in 0..self.width {
-                print!(
-                    "{}",
-                    match self.vals[r][c] {
-                        Thing::Down => "v",
-                        Thing::Left => ">",
-                        Thing::Empty => ".",
-                    }
-                );
+                print!("{}", self.get(r, c).map_or('.', |h| self.herds[h].glyph));
             }
-            println!("")
+            println!()
         }
     }
 }
 
-fn part1(lines: &[&str]) -> i32 {
-    let mut grid = Grid::parse(lines);
+// Clears the bits past width in the last word.
+fn mask_width(bits: &mut [u64], width: usize) {
+    if !width.is_multiple_of(64) {
+        bits[width / 64] &= (1 << (width % 64)) - 1;
+    }
+}
+
+// Sets bit c of out to bit c + by of bits, wrapping around at width, where
+// by is -1, 0 or 1.
+fn rotate(bits: &[u64], width: usize, by: i32, out: &mut [u64]) {
+    match by {
+        1 => rotate_to_low(bits, width, out),
+        -1 => rotate_to_high(bits, width, out),
+        _ => out.copy_from_slice(bits),
+    }
+}
 
-    for i in 0.. {
-        //println!("before step {}", i + 1);
-        //grid.print();
+// Sets bit c of out to bit c + 1 of bits, wrapping around at width.
+fn rotate_to_low(bits: &[u64], width: usize, out: &mut [u64]) {
+    for i in 0..bits.len() {
+        out[i] = bits[i] >> 1 | bits.get(i + 1).map_or(0, |next| next << 63);
+    }
+    let first = bits[0] & 1;
+    out[(width - 1) / 64] |= first << ((width - 1) % 64);
+}
+
+// Sets bit c of out to bit c - 1 of bits, wrapping around at width.
+fn rotate_to_high(bits: &[u64], width: usize, out: &mut [u64]) {
+    for i in 0..bits.len() {
+        out[i] = bits[i] << 1 | if i > 0 { bits[i - 1] >> 63 } else { 0 };
+    }
+    let last = bits[(width - 1) / 64] >> ((width - 1) % 64) & 1;
+    mask_width(out, width);
+    out[0] |= last;
+}
+
+#[derive(Debug, PartialEq)]
+struct StepStats {
+    step: usize,
+    // how many of each herd moved
+    moved: Vec<usize>,
+    largest_jam: usize,
+}
+
+// Steps the grid until nothing moves, returning statistics for every step
+// including the last. If frames is set the grid is printed after each step.
+fn simulate(grid: &mut Grid, frames: bool) -> Vec<StepStats> {
+    let mut stats = vec![];
+    if frames {
+        println!("Initial state:");
+        grid.print();
+    }
+    for step in 1.. {
         let moved = grid.step();
-        if !moved {
-            return i as i32 + 1;
+        if frames {
+            println!();
+            println!("After {} steps:", step);
+            grid.print();
+        }
+        let done = moved.iter().all(|m| *m == 0);
+        stats.push(StepStats {
+            step,
+            moved,
+            largest_jam: grid.largest_jam(),
+        });
+        if done {
+            break;
+        }
+    }
+    stats
+}
+
+fn to_csv(herds: &[Herd], stats: &[StepStats]) -> String {
+    let mut ret = String::from("step");
+    for herd in herds {
+        ret += &format!(",{}_moved", herd.name);
+    }
+    ret += ",largest_jam\n";
+    for s in stats {
+        ret += &s.step.to_string();
+        for m in &s.moved {
+            ret += &format!(",{}", m);
         }
+        ret += &format!(",{}\n", s.largest_jam);
     }
+    ret
+}
 
+// Steps the grid until nothing moves, returning the number of steps taken
+// and the total number of moves.
+fn run(grid: &mut Grid) -> (usize, usize) {
+    let mut total = 0;
+    for step in 1.. {
+        let moved = grid.step().iter().sum::<usize>();
+        if moved == 0 {
+            return (step, total);
+        }
+        total += moved;
+    }
     panic!("unreachable");
 }
 
-fn part2(lines: &[&str]) -> i32 {
-    return 0;
+fn part1(lines: &[&str]) -> i32 {
+    let mut grid = Grid::parse(lines);
+
+    run(&mut grid).0 as i32
+}
+
+fn part2(_lines: &[&str]) -> i32 {
+    0
+}
+
+// number of moves made before the sea cucumbers stop
+fn total_moves(lines: &[&str]) -> usize {
+    let mut grid = Grid::parse(lines);
+
+    run(&mut grid).1
 }
 
 #[cfg(test)]
@@ -132,6 +352,7 @@ mod tests {
         lines: Vec<&'a str>,
         part1: i32,
         part2: i32,
+        total_moves: usize,
     }
 
     #[test]
@@ -150,10 +371,136 @@ mod tests {
             ],
             part1: 58,
             part2: 0,
+            total_moves: 842,
         }];
         for t in cases {
             assert_eq!(part1(&t.lines), t.part1, "part 1 test case {:?}", t.lines);
             assert_eq!(part2(&t.lines), t.part2, "part 2 test case {:?}", t.lines);
+            assert_eq!(
+                total_moves(&t.lines),
+                t.total_moves,
+                "test case {:?}",
+                t.lines
+            );
+        }
+    }
+
+    // the simple cell by cell version, to check the bitsets against
+    fn step_cells(cells: &mut [Vec<char>], herds: &[Herd]) -> usize {
+        let (height, width) = (cells.len() as i32, cells[0].len() as i32);
+        let mut moved = 0;
+        for herd in herds {
+            let mut movers = vec![];
+            for r in 0..height {
+                for c in 0..width {
+                    let n = (
+                        (r + herd.dir.0).rem_euclid(height) as usize,
+                        (c + herd.dir.1).rem_euclid(width) as usize,
+                    );
+                    let p = (r as usize, c as usize);
+                    if cells[p.0][p.1] == herd.glyph && cells[n.0][n.1] == '.' {
+                        movers.push((p, n));
+                    }
+                }
+            }
+            for (p, n) in &movers {
+                cells[p.0][p.1] = '.';
+                cells[n.0][n.1] = herd.glyph;
+            }
+            moved += movers.len();
+        }
+        moved
+    }
+
+    #[test]
+    fn bitsets() {
+        // a small linear congruential generator, so the grids are repeatable
+        let mut seed = 12345u64;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        let herd_sets = [
+            default_herds(),
+            vec![
+                Herd::new("north", '^', (-1, 0)),
+                Herd::new("west", '<', (0, -1)),
+                Herd::new("south", 'v', (1, 0)),
+                Herd::new("east", '>', (0, 1)),
+            ],
+            vec![
+                Herd::new("southeast", '\\', (1, 1)),
+                Herd::new("northeast", '/', (-1, 1)),
+                Herd::new("southwest", 'L', (1, -1)),
+            ],
+        ];
+        for herds in &herd_sets {
+            for (height, width) in [(1, 1), (3, 63), (5, 64), (7, 65), (4, 128), (9, 130)] {
+                let mut cells = (0..height)
+                    .map(|_| {
+                        (0..width)
+                            .map(|_| match random() % (herds.len() + 2) {
+                                0 | 1 => '.',
+                                h => herds[h - 2].glyph,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                let lines = cells
+                    .iter()
+                    .map(|row| row.iter().collect::<String>())
+                    .collect::<Vec<_>>();
+                let lines = lines.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+                let mut grid = Grid::parse_with(&lines, herds.clone());
+                for _ in 0..20 {
+                    let moved = grid.step().iter().sum::<usize>();
+                    assert_eq!(moved, step_cells(&mut cells, herds));
+                    for (r, row) in cells.iter().enumerate() {
+                        for (c, ch) in row.iter().enumerate() {
+                            let expected = herds.iter().position(|h| h.glyph == *ch);
+                            assert_eq!(
+                                grid.get(r, c),
+                                expected,
+                                "{}x{} at {:?}",
+                                height,
+                                width,
+                                (r, c)
+                            );
+                        }
+                    }
+                }
+            }
         }
     }
+
+    #[test]
+    fn report() {
+        assert_eq!(Grid::parse(&[">>>v."]).largest_jam(), 3);
+        assert_eq!(Grid::parse(&[">>>>"]).largest_jam(), 4);
+        assert_eq!(Grid::parse(&["v", "v", ">"]).largest_jam(), 2);
+        assert_eq!(Grid::parse(&["v.", "v.", ".."]).largest_jam(), 0);
+
+        let mut grid = Grid::parse(&[">>.v", ">>>v"]);
+        let stats = simulate(&mut grid, false);
+        assert_eq!(
+            to_csv(&grid.herds, &stats),
+            "step,east_moved,south_moved,largest_jam\n1,1,0,3\n2,1,0,3\n3,0,0,3\n"
+        );
+
+        // moving south first gets the v out of the way
+        let mut grid = Grid::parse(&[">>.v", ">>>."]);
+        assert_eq!(grid.step(), vec![2, 0]);
+        let herds = default_herds().into_iter().rev().collect();
+        let mut grid = Grid::parse_with(&[">>.v", ">>>."], herds);
+        assert_eq!(grid.step(), vec![1, 1]);
+
+        let diagonal = vec![
+            Herd::new("southeast", '\\', (1, 1)),
+            Herd::new("west", '<', (0, -1)),
+        ];
+        let grid = Grid::parse_with(&["\\...", ".\\..", "..\\.", "...<"], diagonal);
+        assert_eq!(grid.largest_jam(), 3);
+    }
 }
@@ -0,0 +1,158 @@
+use std::io;
+use std::io::Write;
+
+// Writes an animated GIF that loops forever. Each frame is width x height
+// indexes into palette, which can have at most 16 colours, and is shown for
+// delay hundredths of a second.
+pub fn write<W: Write>(
+    out: &mut W,
+    width: u16,
+    height: u16,
+    palette: &[[u8; 3]],
+    frames: &[Vec<u8>],
+    delay: u16,
+) -> io::Result<()> {
+    assert!(palette.len() <= 16, "at most 16 colours");
+
+    out.write_all(b"GIF89a")?;
+    out.write_all(&width.to_le_bytes())?;
+    out.write_all(&height.to_le_bytes())?;
+    // global colour table of 16 entries, 8 bits per primary
+    out.write_all(&[0xf3, 0, 0])?;
+    for i in 0..16 {
+        out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+    }
+    // loop forever
+    out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+    for frame in frames {
+        assert_eq!(frame.len(), width as usize * height as usize);
+        out.write_all(&[0x21, 0xf9, 0x04, 0x00])?;
+        out.write_all(&delay.to_le_bytes())?;
+        out.write_all(&[0x00, 0x00])?;
+
+        out.write_all(&[0x2c, 0, 0, 0, 0])?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        out.write_all(&[0x00, MIN_CODE_SIZE])?;
+        for block in lzw(frame).chunks(255) {
+            out.write_all(&[block.len() as u8])?;
+            out.write_all(block)?;
+        }
+        out.write_all(&[0x00])?;
+    }
+
+    out.write_all(&[0x3b])
+}
+
+const MIN_CODE_SIZE: u8 = 4;
+const CLEAR: u32 = 1 << MIN_CODE_SIZE;
+const END: u32 = CLEAR + 1;
+const CODE_BITS: u32 = MIN_CODE_SIZE as u32 + 1;
+// Decoders add a table entry for every code after the first following a
+// clear, and widen codes once the table reaches 32 entries. Clearing this
+// often keeps every code 5 bits, at the cost of no compression.
+const LITERALS_PER_CLEAR: usize = 12;
+
+// LZW encodes pixels using only literal codes.
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let mut codes = vec![];
+    for chunk in pixels.chunks(LITERALS_PER_CLEAR) {
+        codes.push(CLEAR);
+        codes.extend(chunk.iter().map(|p| *p as u32));
+    }
+    codes.push(END);
+
+    // codes are packed least significant bit first
+    let mut ret = vec![];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for code in codes {
+        acc |= code << bits;
+        bits += CODE_BITS;
+        while bits >= 8 {
+            ret.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    }
+    if bits > 0 {
+        ret.push(acc as u8);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decodes GIF LZW data the way a decoder would, widening codes as the
+    // table fills up.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let reset = || (0..=END).map(|c| vec![c as u8]).collect::<Vec<_>>();
+        let (mut table, mut width, mut prev) = (reset(), CODE_BITS, None::<Vec<u8>>);
+        let (mut ret, mut pos) = (vec![], 0);
+        loop {
+            let code = (0..width).fold(0, |code, b| {
+                let bit = (data[(pos + b as usize) / 8] >> ((pos + b as usize) % 8)) & 1;
+                code | (bit as usize) << b
+            });
+            pos += width as usize;
+            if code == CLEAR as usize {
+                table = reset();
+                width = CODE_BITS;
+                prev = None;
+                continue;
+            }
+            if code == END as usize {
+                return ret;
+            }
+            let entry = match (table.get(code), &prev) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(p)) => [&p[..], &p[..1]].concat(),
+                (None, None) => panic!("code {} before any literal", code),
+            };
+            if let Some(p) = prev {
+                table.push([&p[..], &entry[..1]].concat());
+            }
+            ret.extend(&entry);
+            prev = Some(entry);
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn lzw_codes() {
+        // clear, 1, 2, end, five bits each
+        assert_eq!(lzw(&[1, 2]), [0x30, 0x88, 0x08]);
+
+        let pixels = (0..1000).map(|i| (i * 7 % 16) as u8).collect::<Vec<_>>();
+        assert_eq!(unlzw(&lzw(&pixels)), pixels);
+        assert_eq!(unlzw(&lzw(&[])), []);
+    }
+
+    #[test]
+    fn frames() {
+        let palette = [[0, 0, 0], [255, 255, 255], [255, 0, 0]];
+        let frames = [vec![0, 1, 2, 0, 1, 2], vec![2; 6]];
+        let mut out = vec![];
+        write(&mut out, 3, 2, &palette, &frames, 50).unwrap();
+
+        assert_eq!(&out[..13], b"GIF89a\x03\x00\x02\x00\xf3\x00\x00");
+        assert_eq!(&out[13..22], [0, 0, 0, 255, 255, 255, 255, 0, 0]);
+        // the rest of the 16 colours are black, then the loop extension
+        let mut rest = &out[13 + 48 + 19..];
+        for frame in &frames {
+            assert_eq!(&rest[..8], [0x21, 0xf9, 0x04, 0x00, 50, 0, 0, 0]);
+            assert_eq!(&rest[8..18], [0x2c, 0, 0, 0, 0, 3, 0, 2, 0, 0]);
+            assert_eq!(rest[18], MIN_CODE_SIZE);
+            let len = rest[19] as usize;
+            assert_eq!(unlzw(&rest[20..20 + len]), *frame);
+            assert_eq!(rest[20 + len], 0);
+            rest = &rest[21 + len..];
+        }
+        assert_eq!(rest, [0x3b]);
+    }
+}